@@ -0,0 +1,88 @@
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::clock::Clock;
+use crate::tlru::TLRUCache;
+
+/// Number of records evicted per lock, so that a large backlog does not block other users of
+/// the cache for a whole tick.
+const VACUUM_BATCH: usize = 1024;
+
+/// Spawns a task vacuuming `cache` every `interval` until all other references to it are
+/// dropped or its mutex is poisoned.
+///
+/// Expired records are evicted in batches, releasing the lock and yielding in between.
+pub fn spawn_vacuum_task<K, V, C, S>(
+    cache: Arc<Mutex<TLRUCache<K, V, C, S>>>,
+    interval: Duration,
+) -> JoinHandle<()>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    V: Clone + Send + 'static,
+    C: Clock + Send + 'static,
+    C::Instant: Send,
+    S: BuildHasher + Send + 'static,
+{
+    let cache = Arc::downgrade(&cache);
+    tokio::spawn(async move {
+        let mut ticks = time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            loop {
+                let drained = {
+                    let Some(cache) = cache.upgrade() else {
+                        return;
+                    };
+                    let Ok(mut cache) = cache.lock() else {
+                        return;
+                    };
+                    cache.vacuum_limited(VACUUM_BATCH) < VACUUM_BATCH
+                };
+                if drained {
+                    break;
+                }
+                tokio::task::yield_now().await;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use mock_instant::thread_local::MockClock;
+    use tokio::runtime;
+
+    use super::spawn_vacuum_task;
+    use crate::tlru::TLRUCache;
+
+    #[test]
+    fn test_spawn_vacuum_task() {
+        MockClock::set_time(Duration::ZERO);
+        let cache = Arc::new(Mutex::new(TLRUCache::new(Duration::from_secs(1))));
+        for i in 0..2000 {
+            cache.lock().unwrap().insert(&i, i);
+        }
+        MockClock::advance(Duration::from_secs(1));
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let handle = spawn_vacuum_task(cache.clone(), Duration::from_millis(5));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(cache.lock().unwrap().is_empty());
+
+            drop(cache);
+            handle.await.unwrap();
+        });
+    }
+}
@@ -0,0 +1,134 @@
+#[cfg(test)]
+use alloc::rc::Rc;
+#[cfg(test)]
+use core::cell::Cell;
+use core::ops::Sub;
+use core::time::Duration;
+#[cfg(all(feature = "std", not(test)))]
+pub(crate) use std::time::Instant;
+
+#[cfg(all(feature = "std", test))]
+pub(crate) use mock_instant::thread_local::Instant;
+
+#[cfg(not(feature = "std"))]
+pub(crate) type Instant = Duration;
+
+/// Time source used by the caches to stamp and age records.
+///
+/// `DefaultClock` relies on `std::time::Instant`, which is unavailable on
+/// `wasm32-unknown-unknown`. There, construct the caches `with_clock` and a clock reading the
+/// host's monotonic timer instead, for example:
+///
+/// ```ignore
+/// struct PerformanceClock(web_sys::Performance);
+///
+/// impl Clock for PerformanceClock {
+///     type Instant = Duration;
+///
+///     fn now(&self) -> Duration {
+///         Duration::from_secs_f64(self.0.now() / 1000.0)
+///     }
+/// }
+/// ```
+pub trait Clock {
+    type Instant: Copy + Ord + Sub<Output = Duration>;
+
+    fn now(&self) -> Self::Instant;
+
+    /// Time passed since `since`, saturating at zero if `since` lies in the future.
+    fn elapsed(&self, since: Self::Instant) -> Duration {
+        let now = self.now();
+        if now > since {
+            now - since
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// `instant` moved back by `by`, or `None` if that lies before the clock's epoch. Used to
+    /// restore records with their age, clocks keeping this default restore them as new ones.
+    fn checked_sub(&self, _: Self::Instant, _: Duration) -> Option<Self::Instant> {
+        None
+    }
+}
+
+/// Real-time clock backed by `std::time::Instant`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultClock;
+
+#[cfg(feature = "std")]
+impl Clock for DefaultClock {
+    type Instant = Instant;
+
+    fn now(&self) -> Self::Instant {
+        Instant::now()
+    }
+
+    fn checked_sub(&self, instant: Self::Instant, by: Duration) -> Option<Self::Instant> {
+        instant.checked_sub(by)
+    }
+}
+
+/// Stands in for the real-time clock without `std`. It cannot be constructed, so caches have
+/// to be created `with_clock` and a `Clock` reading the platform's monotonic timer.
+#[cfg(not(feature = "std"))]
+#[derive(Debug, Clone, Copy)]
+pub enum DefaultClock {}
+
+#[cfg(not(feature = "std"))]
+impl Clock for DefaultClock {
+    type Instant = Instant;
+
+    fn now(&self) -> Self::Instant {
+        match *self {}
+    }
+
+    fn checked_sub(&self, _: Self::Instant, _: Duration) -> Option<Self::Instant> {
+        match *self {}
+    }
+}
+
+/// Clock the tests set by hand. Clones share the same time, and a ticking clock advances by
+/// its step on every reading.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct ManualClock {
+    now: Rc<Cell<Duration>>,
+    tick: Duration,
+}
+
+#[cfg(test)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+impl ManualClock {
+    pub(crate) fn ticking(tick: Duration) -> Self {
+        ManualClock {
+            now: Rc::default(),
+            tick,
+        }
+    }
+
+    /// Current time without advancing a ticking clock.
+    pub(crate) fn get(&self) -> Duration {
+        self.now.get()
+    }
+
+    pub(crate) fn set(&self, now: Duration) {
+        self.now.set(now);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    type Instant = Duration;
+
+    fn now(&self) -> Duration {
+        let now = self.now.get();
+        self.now.set(now + self.tick);
+        now
+    }
+
+    fn checked_sub(&self, instant: Duration, by: Duration) -> Option<Duration> {
+        instant.checked_sub(by)
+    }
+}
@@ -1,3 +1,23 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub(crate) use std::collections::{hash_map::RandomState, HashMap};
+
+#[cfg(all(not(feature = "std"), not(feature = "hashbrown")))]
+compile_error!("building without `std` requires the `hashbrown` feature");
+
+#[cfg(all(not(feature = "std"), feature = "hashbrown"))]
+pub(crate) use hashbrown::{DefaultHashBuilder as RandomState, HashMap};
+
+#[cfg(feature = "tokio")]
+pub mod background;
+pub mod clock;
 pub mod queue;
+#[cfg(feature = "std")]
+pub mod sharded;
+#[cfg(feature = "std")]
+pub mod sync;
 pub mod tlru;
 pub mod unique_tlru;
@@ -1,121 +1,349 @@
-use std::fmt::Debug;
-use std::marker::PhantomData;
-use std::{fmt, ptr};
+use alloc::boxed::Box;
+use core::fmt;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::NonNull;
 
-pub type NodePtr<T> = *mut Node<T>;
+pub type NodePtr<T> = NonNull<Node<T>>;
+
+type Link<T> = Option<NodePtr<T>>;
 
 pub struct Node<T> {
     pub value: T,
-    prev: NodePtr<T>,
-    next: NodePtr<T>,
+    prev: Link<T>,
+    next: Link<T>,
 }
 
 pub struct Queue<T> {
-    head: NodePtr<T>,
-    tail: NodePtr<T>,
-    _pd: PhantomData<T>,
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+    _pd: PhantomData<Box<Node<T>>>,
 }
 
 impl<T> Queue<T> {
     pub fn new() -> Self {
         Self {
-            head: ptr::null_mut(),
-            tail: ptr::null_mut(),
+            head: None,
+            tail: None,
+            len: 0,
             _pd: PhantomData,
         }
     }
 
     pub fn push(&mut self, value: T) -> NodePtr<T> {
-        let new_tail = Box::into_raw(Box::new(Node {
+        let new_tail = NonNull::from(Box::leak(Box::new(Node {
             value,
-            prev: ptr::null_mut(),
-            next: ptr::null_mut(),
-        }));
-        self.push_node(new_tail);
+            prev: None,
+            next: None,
+        })));
+        unsafe { self.push_node(new_tail) };
         new_tail
     }
 
-    pub fn push_node(&mut self, new_tail: NodePtr<T>) {
-        unsafe {
-            if !self.tail.is_null() {
-                (*self.tail).next = new_tail;
-                (*new_tail).prev = self.tail;
-            } else {
-                self.head = new_tail;
+    /// # Safety
+    ///
+    /// `new_tail` must point to a live node that is not linked into any queue.
+    pub unsafe fn push_node(&mut self, new_tail: NodePtr<T>) {
+        match self.tail {
+            Some(tail) => {
+                (*tail.as_ptr()).next = Some(new_tail);
+                (*new_tail.as_ptr()).prev = Some(tail);
+            }
+            None => self.head = Some(new_tail),
+        }
+        self.tail = Some(new_tail);
+        self.len += 1;
+    }
+
+    /// Like `push_node`, linking `new_head` at the front instead.
+    ///
+    /// # Safety
+    ///
+    /// `new_head` must point to a live node that is not linked into any queue.
+    pub unsafe fn push_node_front(&mut self, new_head: NodePtr<T>) {
+        match self.head {
+            Some(head) => {
+                (*head.as_ptr()).prev = Some(new_head);
+                (*new_head.as_ptr()).next = Some(head);
             }
-            self.tail = new_tail;
+            None => self.tail = Some(new_head),
         }
+        self.head = Some(new_head);
+        self.len += 1;
     }
 
     pub fn peek(&self) -> Option<&T> {
-        unsafe { self.head.as_ref().map(|node| &node.value) }
+        self.head.map(|node| unsafe { &(*node.as_ptr()).value })
     }
 
     pub fn pop_node(&mut self) -> Option<Box<Node<T>>> {
-        unsafe {
-            if self.head.is_null() {
-                None
-            } else {
-                let head = Box::from_raw(self.head);
-                self.head = head.next;
-
-                if self.head.is_null() {
-                    self.tail = ptr::null_mut();
-                }
+        self.head.map(|head| unsafe {
+            let head = Box::from_raw(head.as_ptr());
+            self.head = head.next;
+            match self.head {
+                Some(new_head) => (*new_head.as_ptr()).prev = None,
+                None => self.tail = None,
+            }
+            self.len -= 1;
+            head
+        })
+    }
+
+    pub fn peek_back(&self) -> Option<&T> {
+        self.tail.map(|node| unsafe { &(*node.as_ptr()).value })
+    }
 
-                Some(head)
+    pub fn pop_back(&mut self) -> Option<Box<Node<T>>> {
+        self.tail.map(|tail| unsafe {
+            let tail = Box::from_raw(tail.as_ptr());
+            self.tail = tail.prev;
+            match self.tail {
+                Some(new_tail) => (*new_tail.as_ptr()).next = None,
+                None => self.head = None,
             }
+            self.len -= 1;
+            tail
+        })
+    }
+
+    /// # Safety
+    ///
+    /// `elem` must point to a live node that is linked into this queue or was unlinked from it
+    /// by an earlier `remove`. Removing an unlinked node again is a no-op.
+    pub unsafe fn remove(&mut self, elem: NodePtr<T>) {
+        let node = &mut *elem.as_ptr();
+        if node.prev.is_none() && node.next.is_none() && self.head != Some(elem) {
+            return;
+        }
+        match node.prev {
+            Some(prev) => (*prev.as_ptr()).next = node.next,
+            None => self.head = node.next,
         }
+        match node.next {
+            Some(next) => (*next.as_ptr()).prev = node.prev,
+            None => self.tail = node.prev,
+        }
+        node.prev = None;
+        node.next = None;
+        self.len -= 1;
     }
 
-    pub fn remove(&mut self, elem: NodePtr<T>) {
-        unsafe {
-            if !(*elem).prev.is_null() {
-                (*(*elem).prev).next = (*elem).next;
-            }
-            if !(*elem).next.is_null() {
-                (*(*elem).next).prev = (*elem).prev;
-            }
-            if self.tail == elem {
-                self.tail = (*elem).prev;
-            }
-            if self.head == elem {
-                self.head = (*elem).next;
+    /// Cuts the queue before `node`, returning `node` and everything after it as a new queue.
+    /// Counting the moved nodes takes time linear in their number.
+    ///
+    /// # Safety
+    ///
+    /// `node` must point to a node linked into this queue.
+    pub unsafe fn split_off(&mut self, node: NodePtr<T>) -> Queue<T> {
+        let mut len = 0;
+        let mut elem = Some(node);
+        while let Some(current) = elem {
+            len += 1;
+            elem = (*current.as_ptr()).next;
+        }
+
+        let prev = (*node.as_ptr()).prev.take();
+        match prev {
+            Some(prev) => (*prev.as_ptr()).next = None,
+            None => self.head = None,
+        }
+        let tail = mem::replace(&mut self.tail, prev);
+        self.len -= len;
+
+        Queue {
+            head: Some(node),
+            tail,
+            len,
+            _pd: PhantomData,
+        }
+    }
+
+    /// Moves all nodes of `other` to the back of this queue in constant time, leaving `other`
+    /// empty.
+    pub fn append(&mut self, other: &mut Queue<T>) {
+        let Some(other_head) = other.head.take() else {
+            return;
+        };
+        match self.tail {
+            Some(tail) => unsafe {
+                (*tail.as_ptr()).next = Some(other_head);
+                (*other_head.as_ptr()).prev = Some(tail);
+            },
+            None => self.head = Some(other_head),
+        }
+        self.tail = other.tail.take();
+        self.len += mem::take(&mut other.len);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_node().is_some() {}
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut elem = self.head;
+        while let Some(node) = elem {
+            unsafe {
+                elem = (*node.as_ptr()).next;
+                if !f(&(*node.as_ptr()).value) {
+                    self.remove(node);
+                    drop(Box::from_raw(node.as_ptr()));
+                }
             }
-            (*elem).prev = ptr::null_mut();
-            (*elem).next = ptr::null_mut();
         }
     }
 
     pub fn iter(&self) -> Iter<'_, T> {
         unsafe {
             Iter {
-                next: self.head.as_ref(),
+                front: self.head.map(|node| &*node.as_ptr()),
+                back: self.tail.map(|node| &*node.as_ptr()),
+                len: self.len,
+            }
+        }
+    }
+
+    /// Whether any node holds `value`. Scans from the head, meant for debugging and tests.
+    pub fn contains(&self, value: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter().any(|v| v == value)
+    }
+
+    /// First node from the head whose value satisfies `f`.
+    pub fn find_node<F>(&self, f: F) -> Option<NodePtr<T>>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        unsafe { self.find_node_from(self.head, f) }
+    }
+
+    /// Like `find_node`, starting at `start` instead of the head, so that a scan can resume
+    /// after the node it found last. Finds nothing if `start` is `None`.
+    ///
+    /// # Safety
+    ///
+    /// `start` must point to a node linked into this queue.
+    pub unsafe fn find_node_from<F>(
+        &self,
+        start: Option<NodePtr<T>>,
+        mut f: F,
+    ) -> Option<NodePtr<T>>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut elem = start;
+        while let Some(node) = elem {
+            unsafe {
+                if f(&(*node.as_ptr()).value) {
+                    return Some(node);
+                }
+                elem = (*node.as_ptr()).next;
             }
         }
+        None
+    }
+
+    pub fn head_node(&self) -> Option<NodePtr<T>> {
+        self.head
+    }
+
+    /// # Safety
+    ///
+    /// `node` must point to a node linked into this queue.
+    pub unsafe fn next_node(&self, node: NodePtr<T>) -> Option<NodePtr<T>> {
+        (*node.as_ptr()).next
+    }
+
+    /// Distance from the head of the first node holding `value`. Scans like `contains`.
+    pub fn position(&self, value: &T) -> Option<usize>
+    where
+        T: PartialEq,
+    {
+        self.iter().position(|v| v == value)
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            next: self.head,
+            _pd: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl<T> Drop for Queue<T> {
     fn drop(&mut self) {
-        while let Some(_) = self.pop_node() {}
+        self.clear();
     }
 }
 
 pub struct Iter<'a, T> {
-    next: Option<&'a Node<T>>,
+    front: Option<&'a Node<T>>,
+    back: Option<&'a Node<T>>,
+    len: usize,
 }
 
 impl<'a, T> Iterator for Iter<'a, T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        unsafe {
-            self.next.map(|node| {
-                self.next = node.next.as_ref();
-                &node.value
-            })
+        if self.len == 0 {
+            return None;
+        }
+        self.front.map(|node| {
+            self.len -= 1;
+            self.front = node.next.map(|next| unsafe { &*next.as_ptr() });
+            &node.value
+        })
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
         }
+        self.back.map(|node| {
+            self.len -= 1;
+            self.back = node.prev.map(|prev| unsafe { &*prev.as_ptr() });
+            &node.value
+        })
+    }
+}
+
+pub struct IterMut<'a, T> {
+    next: Link<T>,
+    _pd: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|node| unsafe {
+            let node = &mut *node.as_ptr();
+            self.next = node.next;
+            &mut node.value
+        })
     }
 }
 
@@ -130,6 +358,10 @@ unsafe impl<T: Sync> Sync for Queue<T> {}
 
 #[cfg(test)]
 mod test {
+    use alloc::boxed::Box;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
     use super::Queue;
 
     #[test]
@@ -148,7 +380,77 @@ mod test {
         list.push(2);
         list.push(3);
 
-        assert_eq!(list.iter().map(|x| *x).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_iter_rev() {
+        let mut list = Queue::new();
+        list.push(1);
+        list.push(2);
+        list.push(3);
+
+        assert_eq!(
+            list.iter().rev().copied().collect::<Vec<_>>(),
+            vec![3, 2, 1]
+        );
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), Some(&3));
+        assert_eq!(iter.next_back(), Some(&2));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut list = Queue::new();
+        list.push(1);
+        list.push(2);
+        list.push(3);
+
+        for value in list.iter_mut() {
+            *value *= 2;
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 4, 6]);
+        assert!(Queue::<i32>::new().iter_mut().next().is_none());
+    }
+
+    #[test]
+    fn test_position() {
+        let mut list = Queue::new();
+        list.push(1);
+        let el = list.push(2);
+        list.push(3);
+
+        assert!(list.contains(&3));
+        assert!(!list.contains(&4));
+        assert_eq!(list.position(&1), Some(0));
+        assert_eq!(list.position(&3), Some(2));
+        assert_eq!(list.position(&4), None);
+
+        unsafe {
+            list.remove(el);
+            list.push_node(el);
+        }
+        assert_eq!(list.position(&2), Some(2));
+    }
+
+    #[test]
+    fn test_find_node() {
+        let mut list = Queue::new();
+        list.push(1);
+        let el = list.push(2);
+        list.push(4);
+
+        assert_eq!(list.find_node(|x| x % 2 == 0), Some(el));
+        assert_eq!(list.find_node(|x| *x > 4), None);
+
+        let next = unsafe { list.next_node(el) };
+        assert_eq!(next, unsafe { list.find_node_from(next, |x| x % 2 == 0) });
+        assert_eq!(unsafe { list.find_node_from(None, |_| true) }, None);
+        assert_eq!(list.head_node(), list.find_node(|_| true));
     }
 
     #[test]
@@ -157,15 +459,42 @@ mod test {
         let el1 = list.push(1);
         let el2 = list.push(2);
 
-        list.remove(el1);
-        list.push_node(el1);
+        unsafe {
+            list.remove(el1);
+            list.push_node(el1);
+        }
+
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 1]);
+
+        unsafe {
+            list.remove(el2);
+            list.push_node(el2);
+        }
 
-        assert_eq!(list.iter().map(|x| *x).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_move_to_front() {
+        let mut list = Queue::new();
+        list.push(1);
+        let el = list.push(2);
 
-        list.remove(el2);
-        list.push_node(el2);
+        unsafe {
+            list.remove(el);
+            list.push_node_front(el);
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(list.iter().rev().copied().collect::<Vec<_>>(), vec![1, 2]);
 
-        assert_eq!(list.iter().map(|x| *x).collect::<Vec<_>>(), vec![1, 2]);
+        list.clear();
+        let el = list.push(3);
+        unsafe {
+            list.remove(el);
+            list.push_node_front(el);
+        }
+        assert_eq!(list.peek_back(), Some(&3));
+        assert_eq!(list.len(), 1);
     }
 
     #[test]
@@ -176,9 +505,9 @@ mod test {
         list.push(3);
         list.push(4);
 
-        list.remove(el);
+        unsafe { list.remove(el) };
 
-        assert_eq!(list.iter().map(|x| *x).collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
     }
 
     #[test]
@@ -189,10 +518,12 @@ mod test {
         let el2 = list.push(3);
         list.push(4);
 
-        list.remove(el2);
-        list.remove(el1);
+        unsafe {
+            list.remove(el2);
+            list.remove(el1);
+        }
 
-        assert_eq!(list.iter().map(|x| *x).collect::<Vec<_>>(), vec![1, 4]);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 4]);
     }
 
     #[test]
@@ -203,9 +534,185 @@ mod test {
         list.push(3);
         let el = list.push(4);
 
-        list.remove(el);
+        unsafe { list.remove(el) };
+
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut list = Queue::new();
+        list.push(1);
+        list.push(2);
+        list.push(3);
+        list.push(4);
+        list.push(5);
+
+        list.retain(|x| x % 2 == 0);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 4]);
+
+        list.retain(|_| false);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), Vec::new());
+        list.push(6);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![6]);
+    }
+
+    #[test]
+    fn test_pop_back() {
+        let mut list = Queue::new();
+        list.push(1);
+        list.push(2);
+        list.push(3);
+        assert_eq!(list.peek_back(), Some(&3));
+
+        assert_eq!(list.pop_back().map(|node| node.value), Some(3));
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(list.peek_back(), Some(&2));
 
-        assert_eq!(list.iter().map(|x| *x).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(list.pop_back().map(|node| node.value), Some(2));
+        assert_eq!(list.pop_back().map(|node| node.value), Some(1));
+        assert!(list.pop_back().is_none());
+        assert_eq!(list.peek_back(), None);
+        assert!(list.peek().is_none());
+
+        list.push(4);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![4]);
+    }
+
+    #[test]
+    fn test_len() {
+        let mut list = Queue::new();
+        assert!(list.is_empty());
+        let el1 = list.push(1);
+        let el2 = list.push(2);
+        list.push(3);
+        assert_eq!(list.len(), 3);
+
+        unsafe {
+            list.remove(el2);
+            list.remove(el1);
+        }
+        assert_eq!(list.len(), 1);
+        unsafe { list.push_node(el1) };
+        assert_eq!(list.len(), 2);
+
+        drop(list.pop_node());
+        drop(list.pop_node());
+        assert!(list.pop_node().is_none());
+        assert!(list.is_empty());
+        unsafe { drop(Box::from_raw(el2.as_ptr())) };
+    }
+
+    #[test]
+    fn test_remove_twice() {
+        let mut list = Queue::new();
+        list.push(1);
+        let el = list.push(2);
+        list.push(3);
+
+        unsafe {
+            list.remove(el);
+            list.remove(el);
+        }
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(list.iter().rev().copied().collect::<Vec<_>>(), vec![3, 1]);
+
+        let sole = list.push(4);
+        drop(list.pop_node());
+        drop(list.pop_node());
+        unsafe {
+            list.remove(sole);
+            list.remove(sole);
+        }
+        assert!(list.is_empty());
+        assert!(list.peek().is_none());
+        unsafe {
+            drop(Box::from_raw(el.as_ptr()));
+            drop(Box::from_raw(sole.as_ptr()));
+        }
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut list = Queue::new();
+        let head = list.push(1);
+        let mid = list.push(2);
+        list.push(3);
+        let tail = list.push(4);
+
+        let mut back = unsafe { list.split_off(tail) };
+        assert_eq!(back.iter().copied().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(
+            list.iter().rev().copied().collect::<Vec<_>>(),
+            vec![3, 2, 1]
+        );
+        assert_eq!((list.len(), back.len()), (3, 1));
+
+        let mid_back = unsafe { list.split_off(mid) };
+        assert_eq!(mid_back.iter().copied().collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(
+            mid_back.iter().rev().copied().collect::<Vec<_>>(),
+            vec![3, 2]
+        );
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1]);
+        assert_eq!((list.len(), mid_back.len()), (1, 2));
+
+        let all = unsafe { list.split_off(head) };
+        assert!(list.is_empty());
+        assert!(list.peek().is_none() && list.peek_back().is_none());
+        assert_eq!(all.iter().copied().collect::<Vec<_>>(), vec![1]);
+
+        list.push(5);
+        back.push(6);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![5]);
+        assert_eq!(back.iter().copied().collect::<Vec<_>>(), vec![4, 6]);
+    }
+
+    #[test]
+    fn test_append() {
+        let mut list = Queue::new();
+        let mut other = Queue::new();
+        other.push(1);
+        other.push(2);
+
+        list.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2]);
+
+        list.append(&mut other);
+        assert_eq!(list.len(), 2);
+
+        other.push(3);
+        let el = other.push(4);
+        list.append(&mut other);
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(
+            list.iter().rev().copied().collect::<Vec<_>>(),
+            vec![4, 3, 2, 1]
+        );
+
+        unsafe { list.remove(el) };
+        unsafe { drop(Box::from_raw(el.as_ptr())) };
+        other.push(5);
+        assert_eq!(list.peek_back(), Some(&3));
+        assert_eq!(other.iter().copied().collect::<Vec<_>>(), vec![5]);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut list = Queue::new();
+        list.push(1);
+        list.push(2);
+        list.push(3);
+
+        list.clear();
+        assert!(list.is_empty());
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), Vec::new());
+
+        list.push(4);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![4]);
     }
 
     #[test]
@@ -216,15 +723,15 @@ mod test {
         list.push(3);
 
         assert!(list.pop_node().is_some());
-        assert_eq!(list.iter().map(|x| *x).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 3]);
 
         assert!(list.pop_node().is_some());
-        assert_eq!(list.iter().map(|x| *x).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![3]);
 
         assert!(list.pop_node().is_some());
-        assert_eq!(list.iter().map(|x| *x).collect::<Vec<_>>(), Vec::new());
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), Vec::new());
 
         assert!(list.pop_node().is_none());
-        assert_eq!(list.iter().map(|x| *x).collect::<Vec<_>>(), Vec::new());
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), Vec::new());
     }
 }
@@ -0,0 +1,193 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::tlru::{CacheStats, TLRUCache};
+
+/// Concurrent cache splitting its keys over independently locked `TLRUCache` shards, so that
+/// threads working on different keys rarely contend.
+///
+/// LRU order is tracked per shard, and so is the capacity of a cache created `with_capacity`:
+/// a full shard evicts its own least-recently-used record even if other shards have room.
+pub struct ShardedTLRUCache<K, V> {
+    hasher: RandomState,
+    shards: Box<[Mutex<TLRUCache<K, V>>]>,
+}
+
+impl<K, V> ShardedTLRUCache<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    pub fn new(expiry: Duration, shards: usize) -> Self {
+        Self::from_shards(shards, || TLRUCache::new(expiry))
+    }
+
+    /// Creates a cache whose every shard holds at most `capacity` records, so that the whole
+    /// cache holds at most `shards * capacity`.
+    pub fn with_capacity(expiry: Duration, shards: usize, capacity: usize) -> Self {
+        Self::from_shards(shards, || TLRUCache::with_capacity(expiry, capacity))
+    }
+
+    fn from_shards(shards: usize, shard: impl Fn() -> TLRUCache<K, V>) -> Self {
+        assert!(shards > 0, "ShardedTLRUCache needs at least one shard");
+        Self {
+            hasher: RandomState::new(),
+            shards: (0..shards).map(|_| Mutex::new(shard())).collect(),
+        }
+    }
+
+    pub fn insert(&self, key: &K, value: V) -> Option<V> {
+        self.shard(key).insert(key, value)
+    }
+
+    pub fn fetch<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).fetch(key)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).remove(key)
+    }
+
+    /// Vacuums every shard in turn, holding only one lock at a time, and returns the number of
+    /// evicted records.
+    pub fn vacuum(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().vacuum_count())
+            .sum()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| shard.lock().unwrap().is_empty())
+    }
+
+    /// Sum of the statistics of all shards.
+    pub fn stats(&self) -> CacheStats {
+        self.shards
+            .iter()
+            .fold(CacheStats::default(), |acc, shard| {
+                let stats = shard.lock().unwrap().stats();
+                CacheStats {
+                    hits: acc.hits + stats.hits,
+                    misses: acc.misses + stats.misses,
+                    insertions: acc.insertions + stats.insertions,
+                    evictions: acc.evictions + stats.evictions,
+                }
+            })
+    }
+
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard<Q>(&self, key: &Q) -> MutexGuard<'_, TLRUCache<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        self.shards[index].lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use mock_instant::thread_local::MockClock;
+
+    use super::ShardedTLRUCache;
+
+    #[test]
+    fn test_send_sync() {
+        fn is_send<T: Send>() {}
+        fn is_sync<T: Sync>() {}
+
+        is_send::<ShardedTLRUCache<u32, i32>>();
+        is_sync::<ShardedTLRUCache<u32, i32>>();
+    }
+
+    #[test]
+    fn test_concurrent_insert() {
+        let session = Arc::new(ShardedTLRUCache::new(Duration::from_secs(2), 8));
+        let handles = (0..4)
+            .map(|t| {
+                let session = Arc::clone(&session);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        session.insert(&(t * 100 + i), i);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(session.shards(), 8);
+        assert_eq!(session.len(), 400);
+        assert_eq!(session.fetch(&205), Some(5));
+        assert_eq!(session.remove(&205), Some(5));
+        assert_eq!(session.fetch(&205), None);
+
+        let stats = session.stats();
+        assert_eq!((stats.hits, stats.misses, stats.insertions), (1, 1, 400));
+    }
+
+    #[test]
+    fn test_vacuum() {
+        MockClock::set_time(Duration::ZERO);
+        let session = ShardedTLRUCache::new(Duration::from_secs(2), 4);
+        for i in 0..10 {
+            session.insert(&i, i);
+        }
+        MockClock::advance(Duration::from_secs(1));
+        session.insert(&10, 10);
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.vacuum(), 10);
+
+        assert_eq!(session.len(), 1);
+        assert_eq!(session.fetch(&10), Some(10));
+        assert!(!session.is_empty());
+    }
+
+    #[test]
+    fn test_capacity() {
+        let session = ShardedTLRUCache::with_capacity(Duration::from_secs(2), 4, 3);
+        for i in 0..100 {
+            session.insert(&i, i);
+        }
+        assert_eq!(session.len(), 12);
+        assert_eq!(session.stats().evictions, 88);
+        assert_eq!(session.fetch(&99), Some(99));
+
+        let session = ShardedTLRUCache::with_capacity(Duration::from_secs(2), 1, 2);
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        session.insert(&3, 'c');
+        assert_eq!(session.fetch(&1), None);
+        assert_eq!(session.len(), 2);
+    }
+}
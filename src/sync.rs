@@ -0,0 +1,147 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::clock::{Clock, DefaultClock};
+use crate::tlru::{CacheStats, TLRUCache};
+
+/// Thread-safe `TLRUCache` that locks internally for every call.
+///
+/// All methods return owned values, so nothing borrows from the lock and results can be held
+/// across calls freely.
+pub struct SyncTLRUCache<K, V, C: Clock = DefaultClock, S = RandomState> {
+    inner: Mutex<TLRUCache<K, V, C, S>>,
+}
+
+impl<K, V> SyncTLRUCache<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    pub fn new(expiry: Duration) -> Self {
+        Self::from(TLRUCache::new(expiry))
+    }
+}
+
+impl<K, V, C, S> SyncTLRUCache<K, V, C, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    C: Clock,
+    S: BuildHasher,
+{
+    pub fn insert(&self, key: &K, value: V) -> Option<V> {
+        self.inner.lock().unwrap().insert(key, value)
+    }
+
+    pub fn try_insert(&self, key: &K, value: V) -> Result<(), V> {
+        self.inner.lock().unwrap().try_insert(key, value)
+    }
+
+    pub fn fetch<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.lock().unwrap().fetch(key)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.lock().unwrap().remove(key)
+    }
+
+    /// Vacuums the cache, returning the number of evicted records.
+    pub fn vacuum(&self) -> usize {
+        self.inner.lock().unwrap().vacuum_count()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().unwrap().is_empty()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.inner.lock().unwrap().stats()
+    }
+
+    pub fn into_inner(self) -> TLRUCache<K, V, C, S> {
+        self.inner.into_inner().unwrap()
+    }
+}
+
+impl<K, V, C: Clock, S> From<TLRUCache<K, V, C, S>> for SyncTLRUCache<K, V, C, S> {
+    fn from(cache: TLRUCache<K, V, C, S>) -> Self {
+        Self {
+            inner: Mutex::new(cache),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use mock_instant::thread_local::MockClock;
+
+    use super::SyncTLRUCache;
+
+    #[test]
+    fn test_send_sync() {
+        fn is_send<T: Send>() {}
+        fn is_sync<T: Sync>() {}
+
+        is_send::<SyncTLRUCache<u32, i32>>();
+        is_sync::<SyncTLRUCache<u32, i32>>();
+    }
+
+    #[test]
+    fn test_concurrent_insert() {
+        let session = Arc::new(SyncTLRUCache::new(Duration::from_secs(2)));
+        let handles = (0..4)
+            .map(|t| {
+                let session = Arc::clone(&session);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        session.insert(&(t * 100 + i), i);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(session.len(), 400);
+        assert_eq!(session.fetch(&205), Some(5));
+        assert_eq!(session.remove(&205), Some(5));
+        assert_eq!(session.fetch(&205), None);
+    }
+
+    #[test]
+    fn test_vacuum() {
+        MockClock::set_time(Duration::ZERO);
+        let session = SyncTLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        MockClock::advance(Duration::from_secs(1));
+        session.insert(&2, 'b');
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.vacuum(), 1);
+
+        assert_eq!(session.len(), 1);
+        assert_eq!(
+            session.into_inner().into_iter().collect::<Vec<_>>(),
+            vec![(2, 'b')]
+        );
+    }
+}
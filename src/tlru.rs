@@ -1,30 +1,173 @@
-use std::collections::HashMap;
-use std::fmt;
-use std::fmt::Debug;
-use std::hash::Hash;
-use std::time::Duration;
-#[cfg(not(test))]
-use std::time::Instant;
-
-#[cfg(test)]
-use mock_instant::global::Instant;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::fmt::{Debug, Display};
+use core::hash::{BuildHasher, Hash};
+use core::iter;
+use core::mem;
+use core::ops::Index;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::sync::mpsc::Sender;
 
+use crate::clock::{Clock, DefaultClock, Instant};
 use crate::queue::{self, NodePtr, Queue};
+use crate::{HashMap, RandomState};
 
-pub struct Record<K, V> {
+pub struct Record<K, V, I = Instant> {
     pub key: K,
     pub value: V,
-    pub access: Instant,
+    pub access: I,
+    /// When the record was first inserted. Unlike `access`, lookups and overwrites leave it
+    /// untouched.
+    pub created: I,
+    /// Lifetime overriding the cache-wide expiry for this record.
+    pub ttl: Option<Duration>,
+    /// Number of lookups that found this record since it was first inserted.
+    pub hits: u64,
+    weight: usize,
+}
+
+/// Counters collected by a `TLRUCache` since creation or the last `reset_stats`.
+///
+/// Lookups that refresh a record count as hits or misses, non-mutating lookups such as `peek`
+/// are not recorded. Evictions cover records dropped because they expired or the cache was
+/// full, but not explicit removals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub insertions: u64,
+    pub evictions: u64,
+}
+
+/// Outcome of `fetch_result` on a cache that also remembers absent keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheResult<V> {
+    Hit(V),
+    /// The key was stored as absent with `insert_absent`.
+    NegativeHit,
+    Miss,
+}
+
+/// What a record's lifetime is measured from.
+///
+/// Either way lookups move a record to the back of the LRU order; the policy only decides
+/// whether they also restart its expiry countdown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExpiryPolicy {
+    /// Time-to-idle: records expire once they have not been looked up for the expiry, every
+    /// hit keeps them alive for another full window.
+    #[default]
+    Idle,
+    /// Time-to-live: records expire a fixed time after their first insertion however often
+    /// they are hit or overwritten.
+    ///
+    /// Records then expire out of LRU order, see `vacuum`.
+    Absolute,
+}
+
+/// Why a record was evicted, as reported to the eviction sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionCause {
+    Expired,
+    /// Made room in a cache holding its maximum number of records.
+    Capacity,
+    /// Made the cache light enough for its maximum weight.
+    Weight,
+}
+
+/// What `vacuum_with` does with an expired record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VacuumAction {
+    Evict,
+    /// Refreshes the record's access time and requeues it at the back of the LRU order.
+    Keep,
+}
+
+/// Randomizes record lifetimes by up to `fraction` in either direction, drawing from a
+/// splitmix64 sequence.
+#[derive(Debug, Clone, Copy)]
+struct Jitter {
+    fraction: f64,
+    state: u64,
+}
+
+impl Jitter {
+    fn apply(&mut self, ttl: Duration) -> Duration {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        let unit = (z >> 11) as f64 / (1u64 << 53) as f64;
+        let factor = 1.0 + (2.0 * unit - 1.0) * self.fraction;
+        Duration::try_from_secs_f64(ttl.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+    }
 }
 
-pub struct TLRUCache<K, V> {
+struct Weigher<V> {
+    max_weight: usize,
+    weigh: Arc<dyn Fn(&V) -> usize + Send + Sync>,
+}
+
+impl<V> Clone for Weigher<V> {
+    fn clone(&self) -> Self {
+        Self {
+            max_weight: self.max_weight,
+            weigh: self.weigh.clone(),
+        }
+    }
+}
+
+type RecordPtr<K, V, C> = NodePtr<Record<K, V, <C as Clock>::Instant>>;
+
+/// Next record a vacuum checks under jitter, `None` once it passed the end of the queue.
+type Cursor<K, V, C> = Option<RecordPtr<K, V, C>>;
+
+type MissObserver<K> = Box<dyn FnMut(&K) + Send>;
+
+type Sizer<K, V> = Arc<dyn Fn(&K, &V) -> usize + Send + Sync>;
+
+pub struct TLRUCache<K, V, C: Clock = DefaultClock, S = RandomState> {
+    clock: C,
     expiry: Duration,
-    store: HashMap<K, NodePtr<Record<K, V>>>,
-    order: Queue<Record<K, V>>,
+    capacity: Option<usize>,
+    policy: ExpiryPolicy,
+    max_lifetime: Option<Duration>,
+    stats: CacheStats,
+    #[cfg(feature = "std")]
+    eviction_sender: Option<Sender<(K, V, EvictionCause)>>,
+    miss_observer: Option<MissObserver<K>>,
+    jitter: Option<Jitter>,
+    weigher: Option<Weigher<V>>,
+    weight: usize,
+    sizer: Option<Sizer<K, V>>,
+    store: HashMap<K, RecordPtr<K, V, C>, S>,
+    order: Queue<Record<K, V, C::Instant>>,
+}
+
+unsafe impl<K, V, C, S> Send for TLRUCache<K, V, C, S>
+where
+    K: Send,
+    V: Send,
+    C: Clock + Send,
+    C::Instant: Send,
+    S: Send,
+{
 }
 
-unsafe impl<K: Send + Clone, V: Send> Send for TLRUCache<K, V> {}
-unsafe impl<K: Sync, V: Sync> Sync for TLRUCache<K, V> {}
+unsafe impl<K, V, C, S> Sync for TLRUCache<K, V, C, S>
+where
+    K: Send + Sync,
+    V: Send + Sync,
+    C: Clock + Sync,
+    C::Instant: Sync,
+    S: Sync,
+{
+}
 
 pub struct Iter<'a, T> {
     iter: queue::Iter<'a, T>,
@@ -38,39 +181,413 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 }
 
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+pub struct IterMut<'a, K, V, I> {
+    iter: queue::IterMut<'a, Record<K, V, I>>,
+}
+
+impl<'a, K, V, I> Iterator for IterMut<'a, K, V, I> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|rec| &mut rec.value)
+    }
+}
+
+/// Configures a `TLRUCache`. Unless set, records never expire, the cache is unbounded and
+/// uses `DefaultClock` and `RandomState`.
+pub struct TLRUCacheBuilder<C = DefaultClock, S = RandomState> {
+    expiry: Duration,
+    capacity: Option<usize>,
+    policy: ExpiryPolicy,
+    max_lifetime: Option<Duration>,
+    clock: C,
+    hasher: S,
+}
+
+#[cfg(feature = "std")]
+impl Default for TLRUCacheBuilder {
+    fn default() -> Self {
+        Self {
+            expiry: Duration::MAX,
+            capacity: None,
+            policy: ExpiryPolicy::Idle,
+            max_lifetime: None,
+            clock: DefaultClock,
+            hasher: RandomState::default(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl TLRUCacheBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<C: Clock, S: BuildHasher> TLRUCacheBuilder<C, S> {
+    pub fn expiry(mut self, expiry: Duration) -> Self {
+        self.expiry = expiry;
+        self
+    }
+
+    pub fn capacity(mut self, max_entries: usize) -> Self {
+        self.capacity = Some(max_entries);
+        self
+    }
+
+    pub fn policy(mut self, policy: ExpiryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// See `TLRUCache::set_max_lifetime`.
+    pub fn max_lifetime(mut self, max_lifetime: Duration) -> Self {
+        self.max_lifetime = Some(max_lifetime);
+        self
+    }
+
+    pub fn clock<C2: Clock>(self, clock: C2) -> TLRUCacheBuilder<C2, S> {
+        TLRUCacheBuilder {
+            expiry: self.expiry,
+            capacity: self.capacity,
+            policy: self.policy,
+            max_lifetime: self.max_lifetime,
+            clock,
+            hasher: self.hasher,
+        }
+    }
+
+    pub fn hasher<S2: BuildHasher>(self, hasher: S2) -> TLRUCacheBuilder<C, S2> {
+        TLRUCacheBuilder {
+            expiry: self.expiry,
+            capacity: self.capacity,
+            policy: self.policy,
+            max_lifetime: self.max_lifetime,
+            clock: self.clock,
+            hasher,
+        }
+    }
+
+    pub fn build<K, V>(self) -> TLRUCache<K, V, C, S>
+    where
+        K: Clone + Eq + Hash,
+        V: Clone,
+    {
+        let mut cache = TLRUCache::from_parts(self.expiry, self.clock, self.hasher);
+        cache.capacity = self.capacity;
+        cache.policy = self.policy;
+        cache.max_lifetime = self.max_lifetime;
+        cache
+    }
+}
+
+#[cfg(feature = "std")]
 impl<K, V> TLRUCache<K, V>
 where
     K: Clone + Eq + Hash,
     V: Clone,
 {
     pub fn new(expiry: Duration) -> Self {
+        Self::with_clock(expiry, DefaultClock)
+    }
+
+    /// Creates a cache holding at most `max_entries` records. Inserting a new key into a full
+    /// cache first vacuums all expired records, then evicts the least-recently-used live ones
+    /// while it is still full. With `max_entries` zero nothing is ever stored.
+    pub fn with_capacity(expiry: Duration, max_entries: usize) -> Self {
+        let mut cache = Self::new(expiry);
+        cache.capacity = Some(max_entries);
+        cache
+    }
+
+    /// Creates an unbounded cache whose key map starts with room for `capacity` records. Unlike
+    /// `with_capacity`, this is only an allocation hint and evicts nothing. The LRU queue needs
+    /// no sizing, as its nodes are allocated one by one.
+    pub fn with_hashmap_capacity(expiry: Duration, capacity: usize) -> Self {
+        let mut cache = Self::new(expiry);
+        cache.reserve(capacity);
+        cache
+    }
+
+    /// Creates a cache whose records weigh `weigher(&value)` and together at most `max_weight`.
+    /// Inserting into a cache that becomes too heavy vacuums it first, then evicts the
+    /// least-recently-used records, including the new one if it outweighs `max_weight` on its
+    /// own.
+    ///
+    /// Records are weighed on insertion, changes made through `get_mut` or `iter_mut` are not
+    /// accounted for.
+    pub fn with_weigher<F>(expiry: Duration, max_weight: usize, weigher: F) -> Self
+    where
+        F: Fn(&V) -> usize + Send + Sync + 'static,
+    {
+        let mut cache = Self::new(expiry);
+        cache.weigher = Some(Weigher {
+            max_weight,
+            weigh: Arc::new(weigher),
+        });
+        cache
+    }
+
+    pub fn from_iter_with_expiry<T>(expiry: Duration, iter: T) -> Self
+    where
+        T: IntoIterator<Item = (K, V)>,
+    {
+        let mut cache = Self::new(expiry);
+        cache.extend(iter);
+        cache
+    }
+
+    /// Bulk-loads `map`, stamping every record with the same access time. The resulting LRU
+    /// order follows the map's iteration order and is therefore unspecified.
+    pub fn from_map(expiry: Duration, map: HashMap<K, V>) -> Self {
+        let mut cache = Self::new(expiry);
+        cache.store.reserve(map.len());
+        cache.stats.insertions = map.len() as u64;
+        let now = cache.clock.now();
+        for (key, value) in map {
+            let rec_ptr = cache.order.push(Record {
+                key: key.clone(),
+                value,
+                access: now,
+                created: now,
+                ttl: None,
+                hits: 0,
+                weight: 0,
+            });
+            cache.store.insert(key, rec_ptr);
+        }
+        cache
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> TLRUCache<K, V, DefaultClock, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    S: BuildHasher,
+{
+    pub fn with_hasher(expiry: Duration, hasher: S) -> Self {
+        Self::from_parts(expiry, DefaultClock, hasher)
+    }
+}
+
+impl<K, V, C> TLRUCache<K, V, C>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    C: Clock,
+{
+    pub fn with_clock(expiry: Duration, clock: C) -> Self {
+        Self::from_parts(expiry, clock, RandomState::default())
+    }
+}
+
+impl<K, V, C, S> TLRUCache<K, V, C, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    C: Clock,
+    S: BuildHasher,
+{
+    pub(crate) fn from_parts(expiry: Duration, clock: C, hasher: S) -> Self {
         Self {
+            clock,
             expiry,
-            store: HashMap::new(),
+            capacity: None,
+            policy: ExpiryPolicy::Idle,
+            max_lifetime: None,
+            stats: CacheStats::default(),
+            #[cfg(feature = "std")]
+            eviction_sender: None,
+            miss_observer: None,
+            jitter: None,
+            weigher: None,
+            weight: 0,
+            sizer: None,
+            store: HashMap::with_hasher(hasher),
             order: Queue::new(),
         }
     }
 
-    pub fn insert(&mut self, key: &K, value: V) {
+    /// Sends a copy of every record the cache evicts by itself to `sender`, together with the
+    /// cause: expired records reclaimed by `vacuum` or one of its variants or found by a lookup,
+    /// and records making room for the capacity or weight bound. Records removed explicitly, e.g. by `remove` or
+    /// `pop_lru`, are not sent.
+    ///
+    /// Sending happens synchronously inside the evicting call. Once the receiver is gone,
+    /// records are evicted as before and the send errors are ignored. Clones of the cache do
+    /// not share the sender.
+    #[cfg(feature = "std")]
+    pub fn with_eviction_sender(mut self, sender: Sender<(K, V, EvictionCause)>) -> Self {
+        self.eviction_sender = Some(sender);
+        self
+    }
+
+    /// Like `set_miss_observer`.
+    pub fn with_miss_observer<F>(mut self, observer: F) -> Self
+    where
+        F: FnMut(&K) + Send + 'static,
+    {
+        self.set_miss_observer(observer);
+        self
+    }
+
+    /// Calls `observer` with the key of every lookup that misses because of an expired record,
+    /// and of lookups taking a `&K` that find no record at all, such as `entry` or
+    /// `get_or_insert_with`. Lookups like `fetch` accept a borrowed form of the key, so they
+    /// have no `K` to report for an absent key and only count it in `stats`. Clones of the
+    /// cache do not share the observer.
+    pub fn set_miss_observer<F>(&mut self, observer: F)
+    where
+        F: FnMut(&K) + Send + 'static,
+    {
+        self.miss_observer = Some(Box::new(observer));
+    }
+
+    /// Counts `sizer(&key, &value)` extra bytes per record in `estimated_size`, e.g. for the
+    /// heap buffers of `String` or `Vec` keys and values.
+    pub fn with_sizer<F>(mut self, sizer: F) -> Self
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        self.sizer = Some(Arc::new(sizer));
+        self
+    }
+
+    /// Stretches or shrinks the lifetime of every record inserted from now on by a random
+    /// factor within `1 ± fraction`, so that records inserted together do not all expire at once.
+    ///
+    /// The jittered lifetime is fixed at insertion and overrides the cache-wide expiry, so
+    /// `set_expiry` does not affect those records afterwards. As records no longer expire in
+    /// LRU order, `vacuum` and its variants search the whole queue in one pass, taking time
+    /// linear in the number of stored records. Inserting into a full cache still only reclaims
+    /// the expired records at the head, so it can evict a live record while expired ones are
+    /// stored further back.
+    pub fn with_jitter(self, fraction: f64) -> Self {
+        let seed = RandomState::default().hash_one(fraction.to_bits());
+        self.with_jitter_seed(fraction, seed)
+    }
+
+    /// Like `with_jitter`, drawing the random factors from a sequence determined by `seed`.
+    pub fn with_jitter_seed(mut self, fraction: f64, seed: u64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&fraction),
+            "jitter fraction must lie within 0 and 1"
+        );
+        self.jitter = Some(Jitter {
+            fraction,
+            state: seed,
+        });
+        self
+    }
+
+    pub fn insert(&mut self, key: &K, value: V) -> Option<V> {
+        self.insert_record(key, value, None, None)
+    }
+
+    /// Replaces the value of a live record, returning the previous one. Unlike `insert`, this
+    /// does not count as an access: the record keeps its timestamps and its place in the LRU
+    /// order.
+    ///
+    /// Hands `value` back without inserting if there is no live record, or if it outweighs the
+    /// whole cache on its own like with `OccupiedEntry::insert`. A heavier value can still evict
+    /// other records, but never the updated one.
+    pub fn update(&mut self, key: &K, value: V) -> Result<V, V> {
+        let Some(&ptr) = self.store.get(key) else {
+            return Err(value);
+        };
+        let rec = unsafe { &mut (*ptr.as_ptr()).value };
+        if self.is_expired(rec) || self.outweighs(&value) {
+            return Err(value);
+        }
+        self.weight -= rec.weight;
+        rec.weight = self.weigh(&value);
+        self.weight += rec.weight;
+        let prev = mem::replace(&mut rec.value, value);
+        self.shed_weight(Some(ptr), None);
+        Ok(prev)
+    }
+
+    /// Inserts `value` with its own lifetime instead of the cache-wide expiry. The record can
+    /// then expire out of LRU order, see `vacuum`.
+    pub fn insert_with_ttl(&mut self, key: &K, value: V, ttl: Duration) -> Option<V> {
+        self.insert_record(key, value, Some(ttl), None)
+    }
+
+    /// Like `insert`, returning the records evicted to make room for `value`, oldest first.
+    pub fn insert_capacity(&mut self, key: &K, value: V) -> Vec<(K, V)> {
+        let mut evicted = Vec::new();
+        self.insert_record(key, value, None, Some(&mut evicted));
+        evicted
+    }
+
+    fn insert_record(
+        &mut self,
+        key: &K,
+        value: V,
+        ttl: Option<Duration>,
+        evicted: Option<&mut Vec<(K, V)>>,
+    ) -> Option<V> {
         match self.store.get(key) {
             None => {
-                let rec_ptr = self.order.push(Record {
-                    key: key.clone(),
-                    value,
-                    access: Instant::now(),
-                });
-                self.store.insert(key.clone(), rec_ptr);
+                self.push_record(key.clone(), value, ttl, evicted);
+                None
             }
-            Some(&old) => {
-                unsafe {
-                    (*old).value.access = Instant::now();
+            Some(&old) => unsafe {
+                self.stats.insertions += 1;
+                self.weight -= (*old.as_ptr()).value.weight;
+                (*old.as_ptr()).value.weight = self.weigh(&value);
+                self.weight += (*old.as_ptr()).value.weight;
+                let expired = self.is_expired(&(*old.as_ptr()).value);
+                let prev = mem::replace(&mut (*old.as_ptr()).value.value, value);
+                (*old.as_ptr()).value.access = self.clock.now();
+                // Overwriting a live record keeps its creation time and hits, an expired one
+                // starts over.
+                if expired {
+                    (*old.as_ptr()).value.created = (*old.as_ptr()).value.access;
+                    (*old.as_ptr()).value.hits = 0;
                 }
+                (*old.as_ptr()).value.ttl = self.jittered(ttl);
                 self.order.remove(old);
                 self.order.push_node(old);
+                self.shed_weight(Some(old), evicted);
+                Some(prev)
+            },
+        }
+    }
+
+    /// Inserts the live records of `other` in their LRU order, so they end up most recently
+    /// used. Their lifetimes restart, keeping any per-record TTL, and keys present in both
+    /// caches take the incoming value.
+    pub fn merge<C2: Clock, S2: BuildHasher>(&mut self, mut other: TLRUCache<K, V, C2, S2>) {
+        other.store.clear();
+        while let Some(node) = other.order.pop_node() {
+            if !other.is_expired(&node.value) {
+                let Record {
+                    key, value, ttl, ..
+                } = node.value;
+                self.insert_record(&key, value, ttl, None);
             }
         }
     }
 
+    /// Inserts `value` only if `key` has no live record, otherwise hands `value` back.
+    pub fn try_insert(&mut self, key: &K, value: V) -> Result<(), V> {
+        if self.live_record(key).is_some() {
+            return Err(value);
+        }
+        self.insert_record(key, value, None, None);
+        Ok(())
+    }
+
     pub fn insert_new<KF>(&mut self, generate_random_key: KF, value: V) -> K
     where
         KF: Fn() -> K,
@@ -79,154 +596,2981 @@ where
         while self.store.contains_key(&key) {
             key = generate_random_key();
         }
-        let rec_ptr = self.order.push(Record {
-            key: key.clone(),
-            value,
-            access: Instant::now(),
-        });
-        self.store.insert(key.clone(), rec_ptr);
+        self.push_record(key.clone(), value, None, None);
         key
     }
 
-    pub fn fetch(&mut self, key: &K) -> Option<V> {
-        match self.store.get(key) {
-            None => None,
-            Some(&old) => unsafe {
-                (*old).value.access = Instant::now();
-                self.order.remove(old);
-                self.order.push_node(old);
-                Some((*old).value.value.clone())
-            },
+    /// Like `insert_new`, but gives up after `max_attempts` colliding keys and hands `value`
+    /// back instead of looping forever on a saturated key space.
+    pub fn try_insert_new<KF>(
+        &mut self,
+        generate_random_key: KF,
+        value: V,
+        max_attempts: usize,
+    ) -> Result<K, V>
+    where
+        KF: Fn() -> K,
+    {
+        for _ in 0..max_attempts {
+            let key = generate_random_key();
+            if !self.store.contains_key(&key) {
+                self.push_record(key.clone(), value, None, None);
+                return Ok(key);
+            }
         }
+        Err(value)
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        match self.store.remove(key) {
-            None => None,
-            Some(old) => unsafe {
-                self.order.remove(old);
-                let data = Box::from_raw(old);
-                Some(data.value.value)
-            },
-        }
+    /// Clones the value of a live record and refreshes it. Use `fetch_ref` to borrow the value
+    /// instead, or `probe` to read it without refreshing.
+    pub fn fetch<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.refresh(key)
+            .map(|ptr| unsafe { (*ptr.as_ptr()).value.value.clone() })
     }
 
-    pub fn vacuum(&mut self) -> &mut Self {
-        while let Some(Record { access, .. }) = self.order.peek() {
-            if access.elapsed() < self.expiry {
-                break;
+    /// Like `fetch`, but only refreshes the record once less than `min_remaining` of its lifetime
+    /// is left, sparing frequently read records from being requeued on every lookup.
+    pub fn fetch_lazy_refresh<Q>(&mut self, key: &Q, min_remaining: Duration) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(&ptr) = self.store.get(key) {
+            let rec = unsafe { &mut (*ptr.as_ptr()).value };
+            if !self.is_expired(rec) && self.remaining(rec) >= min_remaining {
+                self.stats.hits += 1;
+                rec.hits += 1;
+                return Some(rec.value.clone());
             }
-            let Record { key, .. } = self.order.pop_node().unwrap().value;
-            _ = self.store.remove(&key);
         }
-        self
+        self.fetch(key)
     }
 
-    pub fn vacuum_callback<F>(&mut self, mut callback: F) -> &mut Self
+    /// Fetches every key in turn, returning the results in input order.
+    pub fn fetch_many<'a, Q>(&mut self, keys: impl IntoIterator<Item = &'a Q>) -> Vec<Option<V>>
     where
-        F: FnMut(Record<K, V>),
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + 'a,
     {
-        while let Some(Record { access, .. }) = self.order.peek() {
-            if access.elapsed() < self.expiry {
-                break;
-            }
-            let rec = self.order.pop_node().unwrap().value;
-            _ = self.store.remove(&rec.key);
-            callback(rec);
-        }
-        self
+        keys.into_iter().map(|key| self.fetch(key)).collect()
     }
 
-    pub fn iter(&self) -> Iter<Record<K, V>> {
-        Iter {
-            iter: self.order.iter(),
-        }
+    /// Like `fetch`, but only marks the record as recently used: its expiry countdown keeps
+    /// running from the previous access.
+    ///
+    /// Records promoted this way expire out of LRU order, see `vacuum`.
+    pub fn fetch_no_refresh<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.promote(key)
+            .map(|ptr| unsafe { (*ptr.as_ptr()).value.value.clone() })
     }
-}
 
-impl<K: Debug, V: Debug> Debug for TLRUCache<K, V>
-where
-    K: Clone,
-    V: Clone,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_map()
-            .entries(
-                self.order
-                    .iter()
-                    .map(|Record { key, value, access }| (key, (access.elapsed(), value))),
-            )
-            .finish()
+    /// Like `fetch`, borrowing the value instead of cloning it.
+    pub fn fetch_ref<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.refresh(key)
+            .map(|ptr| unsafe { &(*ptr.as_ptr()).value.value })
     }
-}
-
-#[cfg(test)]
-mod test {
-    use std::time::Duration;
 
-    use uuid::Uuid;
+    /// Like `fetch_ref`, borrowing the value mutably.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.refresh(key)
+            .map(|ptr| unsafe { &mut (*ptr.as_ptr()).value.value })
+    }
 
-    use super::TLRUCache;
+    /// Refreshes a live record like `fetch` does, without cloning its value.
+    pub fn touch<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.refresh(key).is_some()
+    }
 
-    use mock_instant::global::MockClock;
+    /// Runs `merge` on the live value for `key`, refreshing it like `fetch` does, or stores
+    /// `default` if there is none. The merged value is weighed again.
+    pub fn upsert_with<F>(&mut self, key: &K, default: V, merge: F)
+    where
+        F: FnOnce(&mut V),
+    {
+        match self.refresh_key(key) {
+            Some(ptr) => unsafe {
+                let rec = &mut (*ptr.as_ptr()).value;
+                merge(&mut rec.value);
+                self.weight -= rec.weight;
+                rec.weight = self.weigh(&rec.value);
+                self.weight += rec.weight;
+                self.shed_weight(Some(ptr), None);
+            },
+            None => {
+                self.push_record(key.clone(), default, None, None);
+            }
+        }
+    }
 
-    #[test]
-    fn test_send_sync() {
-        fn is_send<T: Send>() {}
-        fn is_sync<T: Sync>() {}
+    /// Returns the live value for `key`, or stores and returns the one produced by `f`.
+    pub fn get_or_insert_with<F>(&mut self, key: &K, f: F) -> V
+    where
+        F: FnOnce() -> V,
+    {
+        self.entry_or_insert_with(key, f).0
+    }
 
-        is_send::<TLRUCache<Uuid, i32>>();
-        is_sync::<TLRUCache<Uuid, i32>>();
+    /// Like `get_or_insert_with`, also telling whether the value was cached (`true`) or
+    /// loaded by `f` (`false`).
+    pub fn entry_or_insert_with<F>(&mut self, key: &K, f: F) -> (V, bool)
+    where
+        F: FnOnce() -> V,
+    {
+        match self.refresh_key(key) {
+            Some(ptr) => (unsafe { (*ptr.as_ptr()).value.value.clone() }, true),
+            None => {
+                let value = f();
+                self.push_record(key.clone(), value.clone(), None, None);
+                (value, false)
+            }
+        }
     }
 
-    #[test]
-    fn test_insert() {
-        let mut session = TLRUCache::new(Duration::ZERO);
-        session.insert_new(|| Uuid::new_v4(), 1);
-        session.insert_new(|| Uuid::new_v4(), 2);
-        session.insert_new(|| Uuid::new_v4(), 3);
+    /// Like `get_or_insert_with`, but only stores the loaded value if `f` succeeds.
+    pub fn get_or_try_insert_with<E, F>(&mut self, key: &K, f: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        match self.refresh_key(key) {
+            Some(ptr) => Ok(unsafe { (*ptr.as_ptr()).value.value.clone() }),
+            None => {
+                let value = f()?;
+                self.push_record(key.clone(), value.clone(), None, None);
+                Ok(value)
+            }
+        }
+    }
 
-        assert_eq!(
-            session.iter().map(|x| x.value).collect::<Vec<_>>(),
-            vec![1, 2, 3]
-        );
+    /// Looks up `key` for in-place manipulation. A live record is refreshed like `fetch` does,
+    /// an expired one is treated as vacant.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, C, S> {
+        match self.refresh_key(&key) {
+            Some(ptr) => Entry::Occupied(OccupiedEntry { cache: self, ptr }),
+            None => Entry::Vacant(VacantEntry { cache: self, key }),
+        }
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.live_record(key).map(|rec| rec.value.clone())
+    }
+
+    /// The cheapest lookup: borrows the value of a live record without cloning it or touching
+    /// its access time and LRU position. Taking `&self`, it can run under a shared lock.
+    pub fn probe<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.live_record(key).map(|rec| &rec.value)
+    }
+
+    /// Remaining lifetime of `key`, `Duration::ZERO` if it has already expired.
+    pub fn ttl<Q>(&self, key: &Q) -> Option<Duration>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.store
+            .get(key)
+            .map(|&ptr| unsafe { self.remaining(&(*ptr.as_ptr()).value) })
+    }
+
+    /// Lets a live record live `by` longer than it otherwise would, without refreshing it or
+    /// changing its LRU position. Returns whether the record was found.
+    ///
+    /// The extended lifetime is kept as the record's own TTL, so it no longer follows
+    /// `set_expiry` and the record can expire out of LRU order, see `vacuum`.
+    pub fn extend_ttl<Q>(&mut self, key: &Q, by: Duration) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(&ptr) = self.store.get(key) else {
+            return false;
+        };
+        let rec = unsafe { &mut (*ptr.as_ptr()).value };
+        if self.is_expired(rec) {
+            return false;
+        }
+        rec.ttl = Some(rec.ttl.unwrap_or(self.expiry).saturating_add(by));
+        true
+    }
+
+    /// Makes the record for `key` expire right away, returning whether it was stored. The
+    /// record stays stored and is moved to the front of the LRU order, so that the next
+    /// `vacuum` reclaims it.
+    pub fn expire<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(&ptr) = self.store.get(key) else {
+            return false;
+        };
+        unsafe {
+            (*ptr.as_ptr()).value.ttl = Some(Duration::ZERO);
+            self.order.remove(ptr);
+            self.order.push_node_front(ptr);
+        }
+        true
+    }
+
+    /// When a live record was first inserted. Overwriting its value keeps this instant, unless
+    /// the record had already expired.
+    pub fn created_at<Q>(&self, key: &Q) -> Option<C::Instant>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.live_record(key).map(|rec| rec.created)
+    }
+
+    /// When a live record was last inserted or refreshed by a lookup.
+    pub fn last_accessed<Q>(&self, key: &Q) -> Option<C::Instant>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.live_record(key).map(|rec| rec.access)
+    }
+
+    /// How often a live record was found by lookups such as `fetch` or `get_mut` since it was
+    /// first inserted. Like `created_at`, overwriting keeps the count unless the record expired.
+    pub fn hit_count<Q>(&self, key: &Q) -> Option<u64>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.live_record(key).map(|rec| rec.hits)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.live_record(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.store.remove(key) {
+            None => None,
+            Some(old) => unsafe {
+                self.order.remove(old);
+                let data = Box::from_raw(old.as_ptr());
+                self.weight -= data.value.weight;
+                Some(data.value.value)
+            },
+        }
+    }
+
+    /// Removes every key in turn, returning how many were present.
+    pub fn remove_many<'a, Q>(&mut self, keys: impl IntoIterator<Item = &'a Q>) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + 'a,
+    {
+        keys.into_iter()
+            .filter(|key| self.remove(*key).is_some())
+            .count()
+    }
+
+    /// The least-recently-used record, whether or not it has expired.
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        self.order.peek().map(|rec| (&rec.key, &rec.value))
+    }
+
+    /// The most-recently-used record, whether or not it has expired.
+    pub fn peek_mru(&self) -> Option<(&K, &V)> {
+        self.order.peek_back().map(|rec| (&rec.key, &rec.value))
+    }
+
+    /// Removes the least-recently-used record, whether or not it has expired.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let Record {
+            key, value, weight, ..
+        } = self.order.pop_node()?.value;
+        _ = self.store.remove(&key);
+        self.weight -= weight;
+        Some((key, value))
+    }
+
+    /// Evicts expired records from the least-recently-used end of the queue.
+    ///
+    /// The scan stops at the first live record, so it takes time proportional to the number of
+    /// evicted records. Records that expire out of LRU order, e.g. because of their own TTL, an
+    /// absolute expiry or a maximum lifetime, stay stored behind longer-lived ones until those
+    /// go, even though lookups already treat them as absent. `vacuum_all` finds them wherever
+    /// they are queued. With jitter, it searches the whole queue in one pass instead.
+    pub fn vacuum(&mut self) -> &mut Self {
+        self.vacuum_callback(|_| {})
+    }
+
+    /// Like `vacuum`, returning the number of evicted records instead of `self`.
+    pub fn vacuum_count(&mut self) -> usize {
+        let mut count = 0;
+        self.vacuum_callback(|_| count += 1);
+        count
+    }
+
+    /// Like `vacuum`, handing every evicted record to `callback` by value, oldest first.
+    pub fn vacuum_callback<F>(&mut self, mut callback: F) -> &mut Self
+    where
+        F: FnMut(Record<K, V, C::Instant>),
+    {
+        let mut cursor = self.order.head_node();
+        while let Some(rec) = self.pop_expired(&mut cursor) {
+            callback(rec);
+        }
+        self
+    }
+
+    /// Lazily evicts expired records like `vacuum`, yielding them oldest first. Expired records
+    /// not consumed before the iterator is dropped stay stored for a later call.
+    pub fn drain_expired(&mut self) -> impl Iterator<Item = (K, V)> + '_ {
+        let mut cursor = self.order.head_node();
+        iter::from_fn(move || {
+            self.pop_expired(&mut cursor)
+                .map(|rec| (rec.key, rec.value))
+        })
+    }
+
+    /// Like `vacuum`, but scans the whole queue instead of stopping at the first live record,
+    /// so that records with their own shorter lifetime are reclaimed wherever they are queued.
+    /// Takes time linear in the number of stored records rather than expired ones.
+    pub fn vacuum_all(&mut self) -> &mut Self {
+        let expired: Vec<K> = self
+            .order
+            .iter()
+            .filter(|rec| self.is_expired(rec))
+            .map(|rec| rec.key.clone())
+            .collect();
+        for key in expired {
+            if let Some(value) = self.remove(&key) {
+                self.stats.evictions += 1;
+                self.notify_eviction(&key, &value, EvictionCause::Expired);
+            }
+        }
+        self
+    }
+
+    /// Like `vacuum`, letting `f` decide for every expired record whether it is evicted or kept
+    /// for another window, e.g. to pin some records.
+    ///
+    /// `Keep` only restarts the idle countdown, so a kept record can still be expired under
+    /// `ExpiryPolicy::Absolute`, a maximum lifetime or its own zero TTL. Every record is offered
+    /// to `f` at most once per call. With jitter, like `vacuum`, every expired record in the
+    /// queue is offered, not only those at the head.
+    pub fn vacuum_with<F>(&mut self, mut f: F) -> &mut Self
+    where
+        F: FnMut(&Record<K, V, C::Instant>) -> VacuumAction,
+    {
+        if self.jitter.is_some() {
+            // Collected up front, so that kept records requeued at the back are not offered again.
+            let mut expired = Vec::new();
+            let mut cursor = self.order.head_node();
+            while let Some(ptr) = unsafe {
+                self.order
+                    .find_node_from(cursor, |rec| self.is_expired(rec))
+            } {
+                cursor = unsafe { self.order.next_node(ptr) };
+                expired.push(ptr);
+            }
+            for ptr in expired {
+                self.apply_vacuum_action(ptr, &mut f);
+            }
+        } else {
+            for _ in 0..self.order.len() {
+                let Some(ptr) = self.order.head_node() else {
+                    break;
+                };
+                if !self.is_expired(unsafe { &(*ptr.as_ptr()).value }) {
+                    break;
+                }
+                self.apply_vacuum_action(ptr, &mut f);
+            }
+        }
+        self
+    }
+
+    fn apply_vacuum_action<F>(&mut self, ptr: RecordPtr<K, V, C>, f: &mut F)
+    where
+        F: FnMut(&Record<K, V, C::Instant>) -> VacuumAction,
+    {
+        match f(unsafe { &(*ptr.as_ptr()).value }) {
+            VacuumAction::Evict => unsafe {
+                self.order.remove(ptr);
+                self.evict_expired(Box::from_raw(ptr.as_ptr()).value);
+            },
+            VacuumAction::Keep => unsafe {
+                (*ptr.as_ptr()).value.access = self.clock.now();
+                self.order.remove(ptr);
+                self.order.push_node(ptr);
+            },
+        }
+    }
+
+    /// Evicts at most `max` expired records, returning how many were evicted. Whatever is left
+    /// is picked up by the next vacuum.
+    pub fn vacuum_limited(&mut self, max: usize) -> usize {
+        let mut count = 0;
+        let mut cursor = self.order.head_node();
+        while count < max && self.pop_expired(&mut cursor).is_some() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Evicts expired records until `deadline` passes, returning how many were evicted. The
+    /// deadline is checked before each eviction.
+    pub fn vacuum_until(&mut self, deadline: C::Instant) -> usize {
+        let mut count = 0;
+        let mut cursor = self.order.head_node();
+        while self.clock.now() < deadline && self.pop_expired(&mut cursor).is_some() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Removes every record for which `f` returns `false`.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let store = &mut self.store;
+        let weight = &mut self.weight;
+        self.order.retain(|rec| {
+            let keep = f(&rec.key, &rec.value);
+            if !keep {
+                _ = store.remove(&rec.key);
+                *weight -= rec.weight;
+            }
+            keep
+        });
+    }
+
+    /// Empties the cache, yielding owned pairs in LRU order. The cache is left empty even if
+    /// the iterator is dropped early.
+    pub fn drain(&mut self) -> Drain<'_, K, V, C::Instant> {
+        self.store.clear();
+        self.weight = 0;
+        Drain {
+            order: &mut self.order,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.store.clear();
+        self.order.clear();
+        self.weight = 0;
+    }
+
+    pub fn iter(&self) -> Iter<'_, Record<K, V, C::Instant>> {
+        Iter {
+            iter: self.order.iter(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, C::Instant> {
+        IterMut {
+            iter: self.order.iter_mut(),
+        }
+    }
+
+    /// Like `iter`, skipping expired records that have not been vacuumed yet.
+    pub fn iter_live(&self) -> impl Iterator<Item = &Record<K, V, C::Instant>> {
+        self.order.iter().filter(|rec| !self.is_expired(rec))
+    }
+
+    /// Iterates in LRU order like `iter`, pairing every record with its remaining lifetime.
+    pub fn iter_with_ttl(&self) -> impl Iterator<Item = (&K, &V, Duration)> {
+        self.order
+            .iter()
+            .map(|rec| (&rec.key, &rec.value, self.remaining(rec)))
+    }
+
+    /// Keys of live records expiring within `window`, in LRU order, e.g. to reload them ahead
+    /// of time.
+    pub fn expiring_within(&self, window: Duration) -> impl Iterator<Item = &K> {
+        self.iter_with_ttl()
+            .filter(move |(_, _, ttl)| !ttl.is_zero() && *ttl <= window)
+            .map(|(key, _, _)| key)
+    }
+
+    /// Clones every record in LRU order together with its remaining lifetime.
+    pub fn snapshot(&self) -> Vec<(K, V, Duration)> {
+        self.iter_with_ttl()
+            .map(|(key, value, ttl)| (key.clone(), value.clone(), ttl))
+            .collect()
+    }
+
+    /// Iterates in parallel over a snapshot of the records, expired ones included. The records
+    /// are first collected into a `Vec` of references, since the queue cannot be split.
+    #[cfg(feature = "rayon")]
+    pub fn par_iter(&self) -> impl rayon::iter::ParallelIterator<Item = &Record<K, V, C::Instant>>
+    where
+        K: Sync,
+        V: Sync,
+        C::Instant: Sync,
+    {
+        use rayon::iter::IntoParallelIterator;
+
+        self.order.iter().collect::<Vec<_>>().into_par_iter()
+    }
+
+    /// Debug representation listing keys and values in LRU order without the elapsed times the
+    /// `Debug` impl shows, so that it stays the same between calls, e.g. for snapshot tests.
+    pub fn debug_stable(&self) -> DebugStable<'_, K, V, C::Instant> {
+        DebugStable { order: &self.order }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.order.iter().map(|rec| &rec.key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.order.iter().map(|rec| &rec.value)
+    }
+
+    /// Total weight of the stored records, always zero unless created `with_weigher`.
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    /// Approximate heap bytes held by the records: one queue node per record plus the key
+    /// map's buckets. Without `with_sizer`, only these flat struct sizes are counted, not what
+    /// keys and values allocate themselves.
+    pub fn estimated_size(&self) -> usize {
+        let nodes = self.order.len() * mem::size_of::<queue::Node<Record<K, V, C::Instant>>>();
+        // One control byte per bucket besides the entry itself.
+        let buckets = self.store.capacity() * (mem::size_of::<(K, RecordPtr<K, V, C>)>() + 1);
+        let indirect = self.sizer.as_ref().map_or(0, |sizer| {
+            self.order
+                .iter()
+                .map(|rec| sizer(&rec.key, &rec.value))
+                .sum()
+        });
+        nodes + buckets + indirect
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    pub fn expiry(&self) -> Duration {
+        self.expiry
+    }
+
+    pub fn policy(&self) -> ExpiryPolicy {
+        self.policy
+    }
+
+    /// Maximum number of records, `None` unless created `with_capacity` or a bounded builder.
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        self.max_lifetime
+    }
+
+    /// Additionally expires records `max_lifetime` after their creation, however recently they
+    /// were accessed, e.g. to combine an idle timeout with an absolute session limit.
+    ///
+    /// Records reach this limit out of LRU order, see `vacuum`.
+    pub fn set_max_lifetime(&mut self, max_lifetime: Option<Duration>) {
+        self.max_lifetime = max_lifetime;
+    }
+
+    /// Changes the maximum number of records, evicting right away if the cache holds more than
+    /// `capacity`: expired records go first, then the least-recently-used live ones. Returns
+    /// the evicted records, oldest first, and sends them to the eviction sender if one is set.
+    pub fn set_capacity(&mut self, capacity: Option<usize>) -> Vec<(K, V)> {
+        self.capacity = capacity;
+        let mut evicted = Vec::new();
+        if let Some(capacity) = capacity {
+            if self.store.len() > capacity {
+                self.vacuum_callback(|rec| evicted.push((rec.key, rec.value)));
+            }
+            while self.store.len() > capacity
+                && self.evict_lru(EvictionCause::Capacity, None, Some(&mut evicted))
+            {}
+        }
+        evicted
+    }
+
+    /// Records are aged against their last access or creation, so the new window applies to existing
+    /// records as well: lowering it makes them expire sooner, raising it keeps them longer.
+    pub fn set_expiry(&mut self, expiry: Duration) {
+        self.expiry = expiry;
+    }
+
+    /// Number of stored records, including expired ones that have not been vacuumed yet.
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    /// Reserves room for at least `additional` more records in the key map, avoiding rehashes
+    /// during bulk loads.
+    pub fn reserve(&mut self, additional: usize) {
+        self.store.reserve(additional);
+    }
+
+    /// Shrinks the key map to fit the stored records, e.g. to give memory back after a large
+    /// `vacuum`.
+    pub fn shrink_to_fit(&mut self) {
+        self.store.shrink_to_fit();
+    }
+
+    /// Number of records `fetch` would return.
+    ///
+    /// With per-record TTLs an expired record can sit behind a live one, so this scans the whole
+    /// queue rather than stopping at the first live record and is linear in `len`.
+    pub fn live_len(&self) -> usize {
+        self.iter_live().count()
+    }
+
+    fn refresh<Q>(&mut self, key: &Q) -> Option<RecordPtr<K, V, C>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.promote(key)
+            .inspect(|&ptr| unsafe { (*ptr.as_ptr()).value.access = self.clock.now() })
+    }
+
+    /// Like `refresh`, also reporting `key` to the miss observer if it has no record at all.
+    fn refresh_key(&mut self, key: &K) -> Option<RecordPtr<K, V, C>> {
+        if !self.store.contains_key(key) {
+            if let Some(observer) = &mut self.miss_observer {
+                observer(key);
+            }
+        }
+        self.refresh(key)
+    }
+
+    /// Moves a live record to the back of the LRU order without touching its access time.
+    fn promote<Q>(&mut self, key: &Q) -> Option<RecordPtr<K, V, C>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.store.get(key) {
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+            Some(&old) if unsafe { self.is_expired(&(*old.as_ptr()).value) } => {
+                self.stats.misses += 1;
+                if let Some(observer) = &mut self.miss_observer {
+                    observer(unsafe { &(*old.as_ptr()).value.key });
+                }
+                self.stats.evictions += 1;
+                let rec = unsafe { &(*old.as_ptr()).value };
+                self.notify_eviction(&rec.key, &rec.value, EvictionCause::Expired);
+                self.remove(key);
+                None
+            }
+            Some(&old) => unsafe {
+                self.stats.hits += 1;
+                (*old.as_ptr()).value.hits += 1;
+                self.order.remove(old);
+                self.order.push_node(old);
+                Some(old)
+            },
+        }
+    }
+
+    /// Evicts the next expired record. Without jitter only the head can be one, with jitter the
+    /// search resumes at `cursor`, so that a whole vacuum passes over the queue once.
+    fn pop_expired(&mut self, cursor: &mut Cursor<K, V, C>) -> Option<Record<K, V, C::Instant>> {
+        if self.jitter.is_none() {
+            return self.pop_expired_head();
+        }
+        unsafe {
+            let ptr = self
+                .order
+                .find_node_from(*cursor, |rec| self.is_expired(rec))?;
+            *cursor = self.order.next_node(ptr);
+            self.order.remove(ptr);
+            Some(self.evict_expired(Box::from_raw(ptr.as_ptr()).value))
+        }
+    }
+
+    fn pop_expired_head(&mut self) -> Option<Record<K, V, C::Instant>> {
+        if !self.is_expired(self.order.peek()?) {
+            return None;
+        }
+        let rec = self.order.pop_node()?.value;
+        Some(self.evict_expired(rec))
+    }
+
+    /// Accounts for an expired record already unlinked from the LRU order.
+    fn evict_expired(&mut self, rec: Record<K, V, C::Instant>) -> Record<K, V, C::Instant> {
+        _ = self.store.remove(&rec.key);
+        self.weight -= rec.weight;
+        self.stats.evictions += 1;
+        self.notify_eviction(&rec.key, &rec.value, EvictionCause::Expired);
+        rec
+    }
+
+    /// Returns `None` if the new record outweighed the whole cache or the capacity is zero, so
+    /// that it was evicted right away.
+    fn push_record(
+        &mut self,
+        key: K,
+        value: V,
+        ttl: Option<Duration>,
+        mut evicted: Option<&mut Vec<(K, V)>>,
+    ) -> Option<RecordPtr<K, V, C>> {
+        if let Some(capacity) = self.capacity {
+            if self.store.len() >= capacity {
+                self.reclaim_expired(None, evicted.as_deref_mut());
+            }
+            while self.store.len() >= capacity.max(1)
+                && self.evict_lru(EvictionCause::Capacity, None, evicted.as_deref_mut())
+            {
+            }
+        }
+        self.stats.insertions += 1;
+        let now = self.clock.now();
+        let ttl = self.jittered(ttl);
+        let weight = self.weigh(&value);
+        self.weight += weight;
+        let rec_ptr = self.order.push(Record {
+            key: key.clone(),
+            value,
+            access: now,
+            created: now,
+            ttl,
+            hits: 0,
+            weight,
+        });
+        self.store.insert(key, rec_ptr);
+        if self.capacity == Some(0) {
+            self.evict_lru(EvictionCause::Capacity, None, evicted.as_deref_mut());
+        }
+        self.shed_weight(Some(rec_ptr), evicted);
+        // Shedding starts at the head, so the new tail only goes once nothing else is left.
+        (!self.order.is_empty()).then_some(rec_ptr)
+    }
+
+    fn outweighs(&self, value: &V) -> bool {
+        self.weigher
+            .as_ref()
+            .is_some_and(|weigher| (weigher.weigh)(value) > weigher.max_weight)
+    }
+
+    fn weigh(&self, value: &V) -> usize {
+        self.weigher
+            .as_ref()
+            .map_or(0, |weigher| (weigher.weigh)(value))
+    }
+
+    /// Evicts records until the cache is light enough, leaving `keep` stored even if it expired
+    /// or is the least recently used, unless it outweighs the whole cache on its own.
+    fn shed_weight(
+        &mut self,
+        keep: Option<RecordPtr<K, V, C>>,
+        mut evicted: Option<&mut Vec<(K, V)>>,
+    ) {
+        if let Some(max_weight) = self.weigher.as_ref().map(|weigher| weigher.max_weight) {
+            if self.weight > max_weight {
+                self.reclaim_expired(keep, evicted.as_deref_mut());
+            }
+            let keep = keep.filter(|ptr| unsafe { (*ptr.as_ptr()).value.weight } <= max_weight);
+            while self.weight > max_weight
+                && self.evict_lru(EvictionCause::Weight, keep, evicted.as_deref_mut())
+            {}
+        }
+    }
+
+    /// Vacuums the head before a bound forces live records out, handing the expired ones to
+    /// `evicted`. Unlike `vacuum`, this never searches the whole queue, not even with jitter, as
+    /// it runs on every insertion into a full cache. It stops at `keep`, e.g. the record being
+    /// written, which may already be expired under a zero expiry.
+    fn reclaim_expired(
+        &mut self,
+        keep: Option<RecordPtr<K, V, C>>,
+        mut evicted: Option<&mut Vec<(K, V)>>,
+    ) {
+        while keep.is_none() || self.order.head_node() != keep {
+            let Some(rec) = self.pop_expired_head() else {
+                break;
+            };
+            if let Some(evicted) = evicted.as_deref_mut() {
+                evicted.push((rec.key, rec.value));
+            }
+        }
+    }
+
+    /// Evicts the least-recently-used record other than `keep` to make room, handing it to
+    /// `evicted` if given.
+    fn evict_lru(
+        &mut self,
+        cause: EvictionCause,
+        keep: Option<RecordPtr<K, V, C>>,
+        evicted: Option<&mut Vec<(K, V)>>,
+    ) -> bool {
+        // Unlink `keep` for a moment if it is the head, so that the record after it goes instead.
+        let kept = keep.filter(|&keep| self.order.head_node() == Some(keep));
+        if let Some(kept) = kept {
+            unsafe { self.order.remove(kept) };
+        }
+        let pair = self.pop_lru();
+        if let Some(kept) = kept {
+            unsafe { self.order.push_node_front(kept) };
+        }
+        let Some(pair) = pair else {
+            return false;
+        };
+        self.stats.evictions += 1;
+        self.notify_eviction(&pair.0, &pair.1, cause);
+        if let Some(evicted) = evicted {
+            evicted.push(pair);
+        }
+        true
+    }
+
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    fn notify_eviction(&self, key: &K, value: &V, cause: EvictionCause) {
+        #[cfg(feature = "std")]
+        if let Some(sender) = &self.eviction_sender {
+            _ = sender.send((key.clone(), value.clone(), cause));
+        }
+    }
+
+    fn jittered(&mut self, ttl: Option<Duration>) -> Option<Duration> {
+        match &mut self.jitter {
+            Some(jitter) => Some(jitter.apply(ttl.unwrap_or(self.expiry))),
+            None => ttl,
+        }
+    }
+
+    fn is_expired(&self, rec: &Record<K, V, C::Instant>) -> bool {
+        self.remaining(rec).is_zero()
+    }
+
+    fn remaining(&self, rec: &Record<K, V, C::Instant>) -> Duration {
+        let remaining = rec.ttl.unwrap_or(self.expiry).saturating_sub(self.age(rec));
+        match self.max_lifetime {
+            Some(max_lifetime) => {
+                remaining.min(max_lifetime.saturating_sub(self.clock.elapsed(rec.created)))
+            }
+            None => remaining,
+        }
+    }
+
+    fn age(&self, rec: &Record<K, V, C::Instant>) -> Duration {
+        match self.policy {
+            ExpiryPolicy::Idle => self.clock.elapsed(rec.access),
+            ExpiryPolicy::Absolute => self.clock.elapsed(rec.created),
+        }
+    }
+
+    /// Like `fetch`, first handing the value of an expired record to `on_expired` before the
+    /// lookup evicts it.
+    pub(crate) fn fetch_with_expired<Q, F>(&mut self, key: &Q, on_expired: F) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&V),
+    {
+        if let Some(&ptr) = self.store.get(key) {
+            let rec = unsafe { &(*ptr.as_ptr()).value };
+            if self.is_expired(rec) {
+                on_expired(&rec.value);
+            }
+        }
+        self.fetch(key)
+    }
+
+    fn live_record<Q>(&self, key: &Q) -> Option<&Record<K, V, C::Instant>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.store
+            .get(key)
+            .map(|&ptr| unsafe { &(*ptr.as_ptr()).value })
+            .filter(|rec| !self.is_expired(rec))
+    }
+}
+
+/// Negative caching: `None` values mark keys known to be absent, e.g. to avoid asking a
+/// backend about them again until the record expires.
+impl<K, V, C, S> TLRUCache<K, Option<V>, C, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    C: Clock,
+    S: BuildHasher,
+{
+    /// Records `key` as absent for `ttl`.
+    pub fn insert_absent(&mut self, key: &K, ttl: Duration) -> Option<Option<V>> {
+        self.insert_with_ttl(key, None, ttl)
+    }
+
+    /// Like `fetch`, telling absent keys apart from unknown ones.
+    pub fn fetch_result<Q>(&mut self, key: &Q) -> CacheResult<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.fetch(key) {
+            Some(Some(value)) => CacheResult::Hit(value),
+            Some(None) => CacheResult::NegativeHit,
+            None => CacheResult::Miss,
+        }
+    }
+}
+
+pub struct IntoIter<K, V, I> {
+    order: Queue<Record<K, V, I>>,
+}
+
+impl<K, V, I> Iterator for IntoIter<K, V, I> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.order.pop_node().map(|node| {
+            let Record { key, value, .. } = node.value;
+            (key, value)
+        })
+    }
+}
+
+pub enum Entry<'a, K, V, C: Clock, S> {
+    Occupied(OccupiedEntry<'a, K, V, C, S>),
+    Vacant(VacantEntry<'a, K, V, C, S>),
+}
+
+pub struct OccupiedEntry<'a, K, V, C: Clock, S> {
+    cache: &'a mut TLRUCache<K, V, C, S>,
+    ptr: RecordPtr<K, V, C>,
+}
+
+pub struct VacantEntry<'a, K, V, C: Clock, S> {
+    cache: &'a mut TLRUCache<K, V, C, S>,
+    key: K,
+}
+
+impl<'a, K, V, C, S> Entry<'a, K, V, C, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    C: Clock,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Unlike with `HashMap`, this can fail: a vacant entry hands `default` back like
+    /// `VacantEntry::insert` if it outweighs the whole cache on its own or the capacity is zero,
+    /// as storing it would evict it right away. Returning the value lets the caller still use
+    /// it, where `HashMap` would have nothing to point to. Caches without a weigher and with a
+    /// capacity other than zero never fail.
+    pub fn or_insert(self, default: V) -> Result<&'a mut V, V> {
+        self.or_insert_with(|| default)
+    }
+
+    /// See `or_insert`.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> Result<&'a mut V, V> {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+
+    /// Modifies an occupied entry's value and weighs it again, evicting other records if the
+    /// cache became too heavy. A value now outweighing the whole cache is evicted itself,
+    /// leaving the entry vacant.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                entry.reweigh()
+            }
+            vacant => vacant,
+        }
+    }
+}
+
+impl<'a, K, V, C, S> OccupiedEntry<'a, K, V, C, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    C: Clock,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        unsafe { &(*self.ptr.as_ptr()).value.key }
+    }
+
+    pub fn get(&self) -> &V {
+        unsafe { &(*self.ptr.as_ptr()).value.value }
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { &mut (*self.ptr.as_ptr()).value.value }
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        unsafe { &mut (*self.ptr.as_ptr()).value.value }
+    }
+
+    /// Replaces the value, keeping the record's timestamps and LRU position, and evicts other
+    /// records if the cache became too heavy. Returns `value` back instead if it outweighs the
+    /// whole cache on its own.
+    pub fn insert(&mut self, value: V) -> Result<V, V> {
+        if self.cache.outweighs(&value) {
+            return Err(value);
+        }
+        let rec = unsafe { &mut (*self.ptr.as_ptr()).value };
+        self.cache.weight -= rec.weight;
+        rec.weight = self.cache.weigh(&value);
+        self.cache.weight += rec.weight;
+        let prev = mem::replace(&mut rec.value, value);
+        // The entry's record was requeued at the back, so shedding reaches it last and stops
+        // before, as it fits on its own.
+        self.cache.shed_weight(Some(self.ptr), None);
+        Ok(prev)
+    }
+
+    fn reweigh(self) -> Entry<'a, K, V, C, S> {
+        let rec = unsafe { &mut (*self.ptr.as_ptr()).value };
+        if self.cache.outweighs(&rec.value) {
+            let key = rec.key.clone();
+            self.cache.stats.evictions += 1;
+            self.cache
+                .notify_eviction(&key, &rec.value, EvictionCause::Weight);
+            let cache = self.cache;
+            cache.remove(&key);
+            return Entry::Vacant(VacantEntry { cache, key });
+        }
+        self.cache.weight -= rec.weight;
+        rec.weight = self.cache.weigh(&rec.value);
+        self.cache.weight += rec.weight;
+        self.cache.shed_weight(Some(self.ptr), None);
+        Entry::Occupied(self)
+    }
+
+    pub fn remove(self) -> V {
+        unsafe {
+            _ = self.cache.store.remove(&(*self.ptr.as_ptr()).value.key);
+            self.cache.order.remove(self.ptr);
+            let data = Box::from_raw(self.ptr.as_ptr());
+            self.cache.weight -= data.value.weight;
+            data.value.value
+        }
+    }
+}
+
+impl<'a, K, V, C, S> VacantEntry<'a, K, V, C, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    C: Clock,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Stores `value`, evicting other records if the cache became too heavy. Returns `value`
+    /// back instead if it outweighs the whole cache on its own or the capacity is zero.
+    pub fn insert(self, value: V) -> Result<&'a mut V, V> {
+        if self.cache.outweighs(&value) || self.cache.capacity == Some(0) {
+            return Err(value);
+        }
+        let ptr = self
+            .cache
+            .push_record(self.key, value, None, None)
+            .expect("a record within the bounds is never evicted right away");
+        Ok(unsafe { &mut (*ptr.as_ptr()).value.value })
+    }
+}
+
+pub struct Drain<'a, K, V, I> {
+    order: &'a mut Queue<Record<K, V, I>>,
+}
+
+impl<K, V, I> Iterator for Drain<'_, K, V, I> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.order.pop_node().map(|node| {
+            let Record { key, value, .. } = node.value;
+            (key, value)
+        })
+    }
+}
+
+impl<K, V, I> Drop for Drain<'_, K, V, I> {
+    fn drop(&mut self) {
+        self.order.clear();
+    }
+}
+
+impl<K, V, C: Clock, S> IntoIterator for TLRUCache<K, V, C, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, C::Instant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { order: self.order }
+    }
+}
+
+impl<K, V, C, S> Extend<(K, V)> for TLRUCache<K, V, C, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    C: Clock,
+    S: BuildHasher,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (key, value) in iter {
+            self.insert(&key, value);
+        }
+    }
+}
+
+/// Builds a cache whose records never expire (`Duration::MAX`), see `from_iter_with_expiry`.
+#[cfg(feature = "std")]
+impl<K, V> FromIterator<(K, V)> for TLRUCache<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self::from_iter_with_expiry(Duration::MAX, iter)
+    }
+}
+
+/// Looks up a live record like `peek`, leaving LRU order and access time untouched.
+///
+/// # Panics
+///
+/// Panics if `key` has no live record.
+impl<K, V, C, S, Q> Index<&Q> for TLRUCache<K, V, C, S>
+where
+    K: Clone + Eq + Hash + Borrow<Q>,
+    V: Clone,
+    C: Clock,
+    S: BuildHasher,
+    Q: Hash + Eq + ?Sized,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        &self
+            .live_record(key)
+            .expect("no live record for key in TLRUCache")
+            .value
+    }
+}
+
+/// Compares the stored keys and values only. LRU order, timestamps, expiry and statistics are
+/// deliberately ignored, as are whether records have expired.
+impl<K, V, C, S> PartialEq for TLRUCache<K, V, C, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    C: Clock,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.store.len() == other.store.len()
+            && self.order.iter().all(|rec| {
+                other
+                    .store
+                    .get(&rec.key)
+                    .is_some_and(|&ptr| unsafe { (*ptr.as_ptr()).value.value == rec.value })
+            })
+    }
+}
+
+impl<K, V, C, S> Eq for TLRUCache<K, V, C, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    C: Clock,
+    S: BuildHasher,
+{
+}
+
+/// Deep-copies every record into fresh nodes, keeping LRU order, timestamps and statistics.
+/// Neither the miss observer nor the eviction sender is carried over, so evictions from the
+/// copy do not reach the original's receiver.
+impl<K, V, C, S> Clone for TLRUCache<K, V, C, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    C: Clock + Clone,
+    S: BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        let mut cache =
+            Self::from_parts(self.expiry, self.clock.clone(), self.store.hasher().clone());
+        cache.capacity = self.capacity;
+        cache.policy = self.policy;
+        cache.max_lifetime = self.max_lifetime;
+        cache.stats = self.stats;
+        cache.jitter = self.jitter;
+        cache.weigher = self.weigher.clone();
+        cache.sizer = self.sizer.clone();
+        cache.weight = self.weight;
+        for rec in self.order.iter() {
+            let rec_ptr = cache.order.push(Record {
+                key: rec.key.clone(),
+                value: rec.value.clone(),
+                access: rec.access,
+                created: rec.created,
+                ttl: rec.ttl,
+                hits: rec.hits,
+                weight: rec.weight,
+            });
+            cache.store.insert(rec.key.clone(), rec_ptr);
+        }
+        cache
+    }
+}
+
+impl<K: Debug, V: Debug, C: Clock, S> Debug for TLRUCache<K, V, C, S>
+where
+    K: Clone,
+    V: Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.order.iter().map(
+                |Record {
+                     key, value, access, ..
+                 }| { (key, (self.clock.elapsed(*access), value)) },
+            ))
+            .finish()
+    }
+}
+
+pub struct DebugStable<'a, K, V, I> {
+    order: &'a Queue<Record<K, V, I>>,
+}
+
+impl<K: Debug, V: Debug, I> Debug for DebugStable<'_, K, V, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.order.iter().map(|rec| (&rec.key, &rec.value)))
+            .finish()
+    }
+}
+
+/// Number of records listed by the `Display` impl before the rest is summarized.
+const DISPLAY_LIMIT: usize = 10;
+
+/// Compact dump listing the first records in LRU order with their remaining lifetime, like
+/// `tlru(2 entries, expiry=30s): 1=a (12s left), 2=b (30s left)`.
+impl<K, V, C, S> Display for TLRUCache<K, V, C, S>
+where
+    K: Display + Clone + Eq + Hash,
+    V: Display + Clone,
+    C: Clock,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tlru({} entries, expiry={:?})", self.len(), self.expiry)?;
+        for (i, (key, value, ttl)) in self.iter_with_ttl().take(DISPLAY_LIMIT).enumerate() {
+            let sep = if i == 0 { ":" } else { "," };
+            write!(f, "{sep} {key}={value} ({ttl:?} left)")?;
+        }
+        if self.len() > DISPLAY_LIMIT {
+            write!(f, ", ... {} more", self.len() - DISPLAY_LIMIT)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use alloc::vec::Vec;
+    use core::hash::{BuildHasher, Hash};
+    use core::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Record, TLRUCache};
+    use crate::clock::Clock;
+
+    type Entry<K, V> = (K, V, Option<Duration>, Duration);
+
+    /// Serialized as `(expiry, [(key, value, ttl, remaining)])` in LRU order. Records are stored
+    /// with their remaining lifetime since instants cannot be serialized, expired ones are left
+    /// out. The capacity bound, expiry policy, maximum lifetime and statistics are not part of the
+    /// serialized form.
+    impl<K, V, C, S> Serialize for TLRUCache<K, V, C, S>
+    where
+        K: Serialize + Clone + Eq + Hash,
+        V: Serialize + Clone,
+        C: Clock,
+        S: BuildHasher,
+    {
+        fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+            let entries = self
+                .order
+                .iter()
+                .filter(|rec| !self.is_expired(rec))
+                .map(|rec| (&rec.key, &rec.value, rec.ttl, self.remaining(rec)))
+                .collect::<Vec<_>>();
+            (self.expiry, entries).serialize(serializer)
+        }
+    }
+
+    /// Restores each record with its own TTL, if any, and backdates its timestamps so that its
+    /// remaining lifetime carries over. Where that would reach before the clock's epoch, the
+    /// record is stamped with the time of loading and keeps its remaining lifetime as its own
+    /// TTL instead.
+    impl<'de, K, V, C, S> Deserialize<'de> for TLRUCache<K, V, C, S>
+    where
+        K: Deserialize<'de> + Clone + Eq + Hash,
+        V: Deserialize<'de> + Clone,
+        C: Clock + Default,
+        S: BuildHasher + Default,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let (expiry, entries): (Duration, Vec<Entry<K, V>>) =
+                Deserialize::deserialize(deserializer)?;
+            let mut cache = Self::from_parts(expiry, C::default(), S::default());
+            let now = cache.clock.now();
+            for (key, value, ttl, remaining) in entries {
+                if remaining.is_zero() || cache.store.contains_key(&key) {
+                    continue;
+                }
+                let age = ttl.unwrap_or(expiry).saturating_sub(remaining);
+                let (access, ttl) = match cache.clock.checked_sub(now, age) {
+                    Some(access) => (access, ttl),
+                    None => (now, Some(remaining)),
+                };
+                let rec_ptr = cache.order.push(Record {
+                    key: key.clone(),
+                    value,
+                    access,
+                    created: access,
+                    ttl,
+                    hits: 0,
+                    weight: 0,
+                });
+                cache.store.insert(key, rec_ptr);
+            }
+            Ok(cache)
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "std")))]
+mod test_no_std {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    use super::TLRUCache;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::default();
+        let mut session = TLRUCache::with_clock(Duration::from_secs(2), clock.clone());
+        session.insert(&1, 'a');
+        clock.set(Duration::from_secs(1));
+        session.insert(&2, 'b');
+        clock.set(Duration::from_secs(2));
+        assert_eq!(session.vacuum_count(), 1);
+        assert_eq!(session.fetch(&2), Some('b'));
+
+        session.insert(&3, 'c');
+        assert_eq!(session.set_capacity(Some(1)), vec![(2, 'b')]);
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&3]);
+        clock.set(Duration::from_secs(4));
+        assert_eq!(session.fetch(&3), None);
+        assert!(session.is_empty());
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::cell::Cell;
+    use std::collections::{hash_map::DefaultHasher, HashMap};
+    use std::hash::{BuildHasherDefault, Hasher};
+    use std::mem;
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::{
+        CacheResult, CacheStats, Entry, EvictionCause, ExpiryPolicy, TLRUCache, TLRUCacheBuilder,
+        VacuumAction,
+    };
+    use crate::clock::ManualClock;
+
+    use mock_instant::thread_local::MockClock;
+
+    #[test]
+    fn test_send_sync() {
+        fn is_send<T: Send>() {}
+        fn is_sync<T: Sync>() {}
+
+        is_send::<TLRUCache<Uuid, i32>>();
+        is_sync::<TLRUCache<Uuid, i32>>();
+    }
+
+    #[test]
+    fn test_shared_across_threads() {
+        let session = Arc::new(Mutex::new(TLRUCache::new(Duration::from_secs(2))));
+        let key = Uuid::new_v4();
+        let handle = thread::spawn({
+            let session = Arc::clone(&session);
+            move || session.lock().unwrap().insert(&key, 1)
+        });
+        assert_eq!(handle.join().unwrap(), None);
+        assert_eq!(session.lock().unwrap().fetch(&key), Some(1));
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut session = TLRUCache::new(Duration::ZERO);
+        session.insert_new(Uuid::new_v4, 1);
+        session.insert_new(Uuid::new_v4, 2);
+        session.insert_new(Uuid::new_v4, 3);
+
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_insert_existing() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        let k1 = Uuid::new_v4();
+        let k2 = Uuid::new_v4();
+        assert_eq!(session.insert(&k1, 1), None);
+        assert_eq!(session.insert(&k2, 2), None);
+        assert_eq!(session.insert(&k1, 3), Some(1));
+
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(session.fetch(&k1), Some(3));
+    }
+
+    #[test]
+    fn test_iter_rev() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        session.insert_new(Uuid::new_v4, 2);
+        session.insert_new(Uuid::new_v4, 3);
+        session.fetch(&k1);
+
+        assert_eq!(
+            session.iter().rev().map(|x| x.value).collect::<Vec<_>>(),
+            vec![1, 3, 2]
+        );
+    }
+
+    #[test]
+    fn test_fetch() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        let k3 = session.insert_new(Uuid::new_v4, 3);
+        assert_eq!(session.remove(&k2), Some(2));
+        assert_eq!(session.remove(&k2), None);
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+
+        assert_eq!(session.fetch(&k1), Some(1));
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![3, 1]
+        );
+
+        assert_eq!(session.fetch(&k3), Some(3));
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+
+    #[test]
+    fn test_fetch_expired() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        MockClock::advance(Duration::from_millis(1500));
+        assert_eq!(session.fetch(&k2), Some(2));
+
+        MockClock::advance(Duration::from_millis(500));
+        assert_eq!(session.fetch(&k1), None);
+        assert_eq!(session.iter().map(|x| x.value).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(session.fetch(&k2), Some(2));
+    }
+
+    #[test]
+    fn test_contains_key() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        assert!(session.contains_key(&k2));
+        assert!(session.contains_key(&k1));
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        MockClock::advance(Duration::from_secs(2));
+        assert!(!session.contains_key(&k1));
+        assert!(!session.contains_key(&Uuid::new_v4()));
+        assert_eq!(session.len(), 2);
+    }
+
+    #[test]
+    fn test_fetch_lazy_refresh() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(10));
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        let inserted = session.last_accessed(&1);
+
+        MockClock::advance(Duration::from_secs(4));
+        assert_eq!(
+            session.fetch_lazy_refresh(&1, Duration::from_secs(5)),
+            Some('a')
+        );
+        assert_eq!(session.last_accessed(&1), inserted);
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&1, &2]);
+
+        MockClock::advance(Duration::from_secs(2));
+        assert_eq!(
+            session.fetch_lazy_refresh(&1, Duration::from_secs(5)),
+            Some('a')
+        );
+        assert_eq!(session.ttl(&1), Some(Duration::from_secs(10)));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2, &1]);
+        assert_eq!(session.stats().hits, 2);
+    }
+
+    #[test]
+    fn test_fetch_many() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        MockClock::advance(Duration::from_secs(1));
+        session.insert(&2, 'b');
+        session.insert(&3, 'c');
+        MockClock::advance(Duration::from_secs(1));
+
+        assert_eq!(
+            session.fetch_many(&[3, 1, 4, 2]),
+            vec![Some('c'), None, None, Some('b')]
+        );
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&3, &2]);
+    }
+
+    #[test]
+    fn test_fetch_no_refresh() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.fetch_no_refresh(&1), Some('a'));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2, &1]);
+        assert_eq!(session.ttl(&1), Some(Duration::from_secs(1)));
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.fetch_no_refresh(&1), None);
+    }
+
+    #[test]
+    fn test_fetch_ref() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, String::from("a"));
+        session.insert_new(Uuid::new_v4, String::from("b"));
+        assert_eq!(session.fetch_ref(&k1).map(String::as_str), Some("a"));
+        assert_eq!(
+            session.iter().map(|x| x.value.as_str()).collect::<Vec<_>>(),
+            vec!["b", "a"]
+        );
+
+        MockClock::advance(Duration::from_secs(2));
+        assert_eq!(session.fetch_ref(&k1), None);
+        assert_eq!(session.len(), 1);
+    }
+
+    #[test]
+    fn test_borrowed_lookup() {
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&String::from("a"), 1);
+        session.insert(&String::from("b"), 2);
+        assert!(session.contains_key("a"));
+        assert_eq!(session.peek("b"), Some(2));
+        assert_eq!(session.fetch("a"), Some(1));
+        assert_eq!(session.remove("b"), Some(2));
+        assert_eq!(session.fetch("b"), None);
+
+        let mut session: TLRUCache<Arc<str>, i32> = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&Arc::from("a"), 1);
+        assert_eq!(session.fetch("a"), Some(1));
+        assert_eq!(session.get_mut("a"), Some(&mut 1));
+    }
+
+    #[test]
+    fn test_get_mut() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, String::from("a"));
+        session.insert_new(Uuid::new_v4, String::from("b"));
+        session.get_mut(&k1).unwrap().push('c');
+        assert_eq!(
+            session.iter().map(|x| x.value.as_str()).collect::<Vec<_>>(),
+            vec!["b", "ac"]
+        );
+
+        MockClock::advance(Duration::from_secs(2));
+        assert_eq!(session.get_mut(&k1), None);
+    }
+
+    #[test]
+    fn test_extend_ttl() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        MockClock::advance(Duration::from_secs(1));
+        assert!(session.extend_ttl(&1, Duration::from_secs(3)));
+        assert!(!session.extend_ttl(&3, Duration::from_secs(3)));
+        assert_eq!(session.ttl(&1), Some(Duration::from_secs(4)));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&1, &2]);
+
+        MockClock::advance(Duration::from_secs(3));
+        assert_eq!(session.peek(&1), Some('a'));
+        assert!(!session.extend_ttl(&2, Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        session.insert(&3, 'c');
+        assert!(session.expire(&2));
+        assert!(!session.expire(&4));
+        assert_eq!(session.len(), 3);
+        assert_eq!(session.fetch(&2), None);
+
+        session.insert(&2, 'b');
+        session.expire(&2);
+        assert_eq!(session.vacuum_count(), 1);
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&1, &3]);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let clock = ManualClock::default();
+        let mut session = TLRUCache::with_clock(Duration::from_secs(5), clock.clone());
+        session.insert(&1, 'a');
+        clock.set(Duration::from_secs(2));
+        session.fetch(&1);
+        assert_eq!(session.created_at(&1), Some(Duration::ZERO));
+        assert_eq!(session.last_accessed(&1), Some(Duration::from_secs(2)));
+
+        clock.set(Duration::from_secs(3));
+        session.fetch_no_refresh(&1);
+        assert_eq!(session.last_accessed(&1), Some(Duration::from_secs(2)));
+        assert_eq!(session.created_at(&2), None);
+
+        session.insert(&1, 'b');
+        assert_eq!(session.created_at(&1), Some(Duration::ZERO));
+        assert_eq!(session.last_accessed(&1), Some(Duration::from_secs(3)));
+
+        clock.set(Duration::from_secs(8));
+        session.insert(&1, 'c');
+        assert_eq!(session.created_at(&1), Some(Duration::from_secs(8)));
+    }
+
+    #[test]
+    fn test_hit_count() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        session.insert(&1, 'a');
+        assert_eq!(session.hit_count(&1), Some(0));
+        session.fetch(&1);
+        session.get_mut(&1);
+        session.peek(&1);
+        assert_eq!(session.hit_count(&1), Some(2));
+        assert_eq!(session.iter().map(|x| x.hits).collect::<Vec<_>>(), vec![2]);
+
+        session.insert(&1, 'b');
+        assert_eq!(session.hit_count(&1), Some(2));
+        assert_eq!(session.hit_count(&2), None);
+
+        MockClock::advance(Duration::from_secs(1));
+        session.insert(&1, 'c');
+        assert_eq!(session.hit_count(&1), Some(0));
+    }
+
+    #[test]
+    fn test_touch() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        session.insert_new(Uuid::new_v4, 2);
+        MockClock::advance(Duration::from_secs(1));
+        assert!(session.touch(&k1));
+        assert_eq!(session.ttl(&k1), Some(Duration::from_secs(2)));
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+
+        MockClock::advance(Duration::from_secs(2));
+        assert!(!session.touch(&k1));
+        assert!(!session.touch(&Uuid::new_v4()));
+        assert_eq!(session.len(), 1);
+    }
+
+    #[test]
+    fn test_entry() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        *session.entry(1).or_insert(0).unwrap() += 1;
+        *session.entry(1).or_insert(0).unwrap() += 1;
+        _ = session
+            .entry(2)
+            .and_modify(|v| *v += 10)
+            .or_insert_with(|| 5);
+        _ = session
+            .entry(2)
+            .and_modify(|v| *v += 10)
+            .or_insert_with(|| 5);
+        assert_eq!(session.peek(&1), Some(2));
+        assert_eq!(session.peek(&2), Some(15));
+
+        MockClock::advance(Duration::from_secs(1));
+        match session.entry(1) {
+            Entry::Occupied(mut entry) => assert_eq!(entry.insert(7), Ok(2)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2, &1]);
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.entry(2).or_insert(0), Ok(&mut 0));
+        match session.entry(1) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), 7),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(session.entry(3).key(), &3);
+        assert_eq!(session.len(), 1);
+    }
+
+    #[test]
+    fn test_entry_weight() {
+        let mut session = TLRUCache::with_weigher(Duration::from_secs(1), 4, String::len);
+        session.insert(&1, String::from("a"));
+        session.insert(&2, String::from("b"));
+        match session.entry(2) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(
+                    entry.insert(String::from("ccccc")),
+                    Err(String::from("ccccc"))
+                );
+                assert_eq!(entry.insert(String::from("cccc")), Ok(String::from("b")));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(session.weight(), 4);
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2]);
+
+        let entry = session.entry(2).and_modify(|v| v.push('c'));
+        assert!(matches!(entry, Entry::Vacant(_)));
+        assert_eq!(
+            entry.or_insert(String::from("ddddd")),
+            Err(String::from("ddddd"))
+        );
+        assert!(session.is_empty());
+        assert_eq!(session.weight(), 0);
+
+        session.insert(&1, String::from("aa"));
+        _ = session.entry(3).or_insert(String::from("bb"));
+        _ = session.entry(1).and_modify(|v| v.push('a'));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&1]);
+        assert_eq!(session.weight(), 3);
+
+        let mut session = TLRUCache::with_weigher(Duration::ZERO, 10, |v: &usize| *v);
+        session.insert(&1, 6);
+        assert_eq!(session.entry(2).or_insert(6), Ok(&mut 6));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2]);
+        assert_eq!(session.weight(), 6);
+    }
+
+    #[test]
+    fn test_peek() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        session.insert_new(Uuid::new_v4, 2);
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.peek(&k1), Some(1));
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.peek(&k1), None);
+    }
+
+    #[test]
+    fn test_index() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        session.insert(&String::from("a"), 1);
+        session.insert(&String::from("b"), 2);
+        assert_eq!(session["a"], 1);
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no live record")]
+    fn test_index_expired() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        session.insert(&1, 'a');
+        MockClock::advance(Duration::from_secs(1));
+        _ = session[&1];
+    }
+
+    #[test]
+    fn test_probe() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, String::from("a"));
+        session.insert(&2, String::from("b"));
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.probe(&1).map(String::as_str), Some("a"));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&1, &2]);
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.probe(&1), None);
+        assert_eq!(session.stats().hits, 0);
+    }
+
+    #[test]
+    fn test_ttl() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.ttl(&k1), Some(Duration::from_secs(1)));
+        assert_eq!(session.ttl(&Uuid::new_v4()), None);
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        session.fetch(&k2);
+        MockClock::advance(Duration::from_millis(1500));
+        assert_eq!(session.ttl(&k1), Some(Duration::ZERO));
+        assert_eq!(session.ttl(&k2), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        session.insert_new(Uuid::new_v4, 2);
+        session.clear();
+        assert!(session.is_empty());
+        assert_eq!(session.fetch(&k1), None);
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            Vec::new()
+        );
+
+        session.insert(&k1, 3);
+        session.insert_new(Uuid::new_v4, 4);
+        assert_eq!(session.len(), 2);
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+    }
+
+    #[test]
+    fn test_custom_clock() {
+        let clock = ManualClock::default();
+        let mut session = TLRUCache::with_clock(Duration::from_secs(2), clock.clone());
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        clock.set(Duration::from_secs(1));
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        clock.set(Duration::from_secs(2));
+        assert_eq!(session.fetch(&k1), None);
+        assert_eq!(session.fetch(&k2), Some(2));
+    }
+
+    #[test]
+    fn test_capacity() {
+        assert_eq!(
+            TLRUCache::<i32, i32>::new(Duration::from_secs(1)).capacity(),
+            None
+        );
+        let mut session = TLRUCache::with_capacity(Duration::from_secs(1), 2);
+        assert_eq!(session.capacity(), Some(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        session.fetch(&k1);
+        let k3 = session.insert_new(Uuid::new_v4, 3);
+        assert_eq!(session.len(), 2);
+        assert_eq!(session.fetch(&k2), None);
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+
+        session.insert(&k3, 4);
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![1, 4]
+        );
+        session.insert(&k2, 5);
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+    }
+
+    #[test]
+    fn test_hasher() {
+        #[derive(Default)]
+        struct IdentityHasher(u64);
+
+        impl Hasher for IdentityHasher {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+
+            fn write(&mut self, bytes: &[u8]) {
+                for &b in bytes {
+                    self.0 = (self.0 << 8) | b as u64;
+                }
+            }
+        }
+
+        let mut session = TLRUCache::with_hasher(
+            Duration::from_secs(1),
+            BuildHasherDefault::<IdentityHasher>::default(),
+        );
+        session.insert(&1u64, 1);
+        session.insert(&2u64, 2);
+        assert_eq!(session.fetch(&1), Some(1));
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+    }
+
+    #[test]
+    fn test_set_expiry() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert_new(Uuid::new_v4, 1);
+        MockClock::advance(Duration::from_secs(1));
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        MockClock::advance(Duration::from_millis(500));
+        session.vacuum();
+        assert_eq!(session.len(), 2);
+
+        session.set_expiry(Duration::from_secs(1));
+        assert_eq!(session.expiry(), Duration::from_secs(1));
+        session.vacuum();
+        assert_eq!(session.iter().map(|x| x.value).collect::<Vec<_>>(), vec![2]);
+
+        session.set_expiry(Duration::from_secs(5));
+        MockClock::advance(Duration::from_secs(3));
+        assert_eq!(session.vacuum().fetch(&k2), Some(2));
+    }
+
+    #[test]
+    fn test_insert_capacity() {
+        let mut session = TLRUCache::with_capacity(Duration::from_secs(1), 2);
+        assert_eq!(session.insert_capacity(&1, 'a'), vec![]);
+        assert_eq!(session.insert_capacity(&2, 'b'), vec![]);
+        assert_eq!(session.insert_capacity(&3, 'c'), vec![(1, 'a')]);
+        assert_eq!(session.insert_capacity(&3, 'd'), vec![]);
+
+        let mut session = TLRUCache::with_weigher(Duration::from_secs(1), 4, String::len);
+        session.insert(&1, String::from("a"));
+        session.insert(&2, String::from("bb"));
+        assert_eq!(
+            session.insert_capacity(&3, String::from("cccc")),
+            vec![(1, String::from("a")), (2, String::from("bb"))]
+        );
+        assert_eq!(session.stats().evictions, 2);
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut session = TLRUCache::with_capacity(Duration::from_secs(1), 0);
+        assert_eq!(session.insert(&1, 'a'), None);
+        assert!(session.is_empty());
+        assert_eq!(session.insert_capacity(&2, 'b'), vec![(2, 'b')]);
+        assert_eq!(session.get_or_insert_with(&3, || 'c'), 'c');
+        assert_eq!(session.entry(4).or_insert('d'), Err('d'));
+        assert!(session.is_empty());
+        assert_eq!(session.stats().evictions, 3);
+    }
+
+    #[test]
+    fn test_set_capacity() {
+        let (sender, receiver) = mpsc::channel();
+        let mut session =
+            TLRUCache::with_capacity(Duration::from_secs(1), 4).with_eviction_sender(sender);
+        session.extend((0..4).map(|i| (i, i)));
+        session.fetch(&0);
+
+        assert_eq!(session.set_capacity(Some(2)), vec![(1, 1), (2, 2)]);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                (1, 1, EvictionCause::Capacity),
+                (2, 2, EvictionCause::Capacity)
+            ]
+        );
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&3, &0]);
+        assert_eq!(session.stats().evictions, 2);
+
+        assert_eq!(session.set_capacity(None), vec![]);
+        session.extend((4..8).map(|i| (i, i)));
+        assert_eq!(session.len(), 6);
+        assert_eq!(session.set_capacity(Some(10)), vec![]);
+        assert_eq!(session.capacity(), Some(10));
+    }
+
+    #[test]
+    fn test_capacity_reclaims_expired_first() {
+        MockClock::set_time(Duration::ZERO);
+        let (sender, receiver) = mpsc::channel();
+        let mut session =
+            TLRUCache::with_capacity(Duration::from_secs(1), 3).with_eviction_sender(sender);
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        MockClock::advance(Duration::from_millis(500));
+        session.insert(&3, 'c');
+        MockClock::advance(Duration::from_millis(600));
+
+        assert_eq!(session.insert_capacity(&4, 'd'), vec![(1, 'a'), (2, 'b')]);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                (1, 'a', EvictionCause::Expired),
+                (2, 'b', EvictionCause::Expired)
+            ]
+        );
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec!['c', 'd']
+        );
+        assert_eq!(session.stats().evictions, 2);
+    }
+
+    #[test]
+    fn test_miss_observer() {
+        MockClock::set_time(Duration::ZERO);
+        let misses = Arc::new(Mutex::new(Vec::new()));
+        let observed = misses.clone();
+        let mut session = TLRUCache::new(Duration::from_secs(1))
+            .with_miss_observer(move |key: &String| observed.lock().unwrap().push(key.clone()));
+        session.insert(&String::from("a"), 1);
+
+        assert_eq!(session.fetch("a"), Some(1));
+        assert_eq!(session.fetch("b"), None);
+        assert_eq!(session.stats().misses, 1);
+        assert_eq!(session.get_or_insert_with(&String::from("c"), || 3), 3);
+        assert_eq!(session.get_or_insert_with(&String::from("c"), || 4), 3);
+        assert_eq!(*misses.lock().unwrap(), vec!["c"]);
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.fetch("a"), None);
+        assert_eq!(*misses.lock().unwrap(), vec!["c", "a"]);
+
+        let count = Arc::new(Mutex::new(0));
+        let counter = count.clone();
+        session.set_miss_observer(move |_| *counter.lock().unwrap() += 1);
+        assert!(matches!(session.entry(String::from("d")), Entry::Vacant(_)));
+        assert_eq!(*count.lock().unwrap(), 1);
+        assert_eq!(misses.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_estimated_size() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        assert_eq!(session.estimated_size(), 0);
+        session.insert(&1, String::from("abc"));
+        let flat = session.estimated_size();
+        assert!(flat >= mem::size_of::<(i32, String)>());
+
+        let session = session.with_sizer(|_, value| value.capacity());
+        assert_eq!(session.estimated_size(), flat + 3);
+        assert_eq!(session.clone().estimated_size(), session.estimated_size());
+    }
+
+    #[test]
+    fn test_weigher() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::with_weigher(Duration::from_secs(1), 10, String::len);
+        session.insert(&1, String::from("aaaa"));
+        session.insert(&2, String::from("bbbb"));
+        assert_eq!(session.weight(), 8);
+
+        session.insert(&3, String::from("ccc"));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2, &3]);
+        assert_eq!(session.weight(), 7);
+
+        session.insert(&2, String::from("b"));
+        assert_eq!(session.weight(), 4);
+        session.remove(&3);
+        assert_eq!(session.weight(), 1);
+
+        session.insert(&4, String::from("dddddddddddd"));
+        assert!(session.is_empty());
+        assert_eq!(session.weight(), 0);
+
+        session.insert(&5, String::from("ee"));
+        MockClock::advance(Duration::from_secs(1));
+        session.vacuum();
+        assert_eq!(session.weight(), 0);
+    }
+
+    #[test]
+    fn test_update() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        MockClock::advance(Duration::from_secs(1));
+
+        assert_eq!(session.update(&1, 'c'), Ok('a'));
+        assert_eq!(session.update(&3, 'd'), Err('d'));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&1, &2]);
+        assert_eq!(session.peek(&1), Some('c'));
+        assert_eq!(session.len(), 2);
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.update(&1, 'e'), Err('e'));
+        assert_eq!(session.peek(&1), None);
+
+        let mut session = TLRUCache::with_weigher(Duration::from_secs(2), 4, String::len);
+        session.insert(&1, String::from("a"));
+        session.insert(&2, String::from("b"));
+        assert_eq!(
+            session.update(&1, String::from("aaaaa")),
+            Err(String::from("aaaaa"))
+        );
+        assert_eq!(
+            session.update(&1, String::from("aaaa")),
+            Ok(String::from("a"))
+        );
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&1]);
+        assert_eq!(session.weight(), 4);
+    }
+
+    #[test]
+    fn test_insert_with_ttl() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = Uuid::new_v4();
+        let k2 = Uuid::new_v4();
+        let k3 = Uuid::new_v4();
+        session.insert_with_ttl(&k1, 1, Duration::from_millis(500));
+        session.insert(&k2, 2);
+        session.insert_with_ttl(&k3, 3, Duration::from_secs(5));
+        assert_eq!(session.ttl(&k1), Some(Duration::from_millis(500)));
+        assert_eq!(session.ttl(&k3), Some(Duration::from_secs(5)));
+
+        MockClock::advance(Duration::from_secs(1));
+        session.vacuum();
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+
+        MockClock::advance(Duration::from_secs(1));
+        session.vacuum();
+        assert_eq!(session.iter().map(|x| x.value).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(session.fetch(&k3), Some(3));
+
+        session.insert(&k3, 4);
+        assert_eq!(session.ttl(&k3), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_jitter() {
+        MockClock::set_time(Duration::ZERO);
+        let ttls = |seed| {
+            let mut session = TLRUCache::new(Duration::from_secs(10)).with_jitter_seed(0.5, seed);
+            for i in 0..100 {
+                session.insert(&i, i);
+            }
+            session.insert(&0, 0);
+            session
+                .iter_with_ttl()
+                .map(|(_, _, ttl)| ttl)
+                .collect::<Vec<_>>()
+        };
+
+        let jittered = ttls(42);
+        assert!(jittered
+            .iter()
+            .all(|ttl| (Duration::from_secs(5)..=Duration::from_secs(15)).contains(ttl)));
+        assert!(jittered.iter().any(|ttl| *ttl < Duration::from_secs(9)));
+        assert!(jittered.iter().any(|ttl| *ttl > Duration::from_secs(11)));
+        assert_eq!(jittered, ttls(42));
+        assert_ne!(jittered, ttls(43));
+    }
+
+    #[test]
+    fn test_jitter_vacuum() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(10)).with_jitter_seed(0.5, 42);
+        session.extend((0..1000).map(|i| (i, i)));
+        MockClock::advance(Duration::from_secs(12));
+        let live = session.live_len();
+        assert!(live > 0 && live < 1000);
+
+        assert_eq!(session.vacuum_limited(10), 10);
+        assert_eq!(session.drain_expired().take(10).count(), 10);
+        assert_eq!(session.vacuum_count(), 1000 - live - 20);
+        assert_eq!(session.len(), live);
+        assert_eq!(session.live_len(), live);
+
+        let mut session =
+            TLRUCache::with_capacity(Duration::from_secs(10), 3).with_jitter_seed(0.0, 0);
+        session.insert(&1, 'a');
+        session.insert_with_ttl(&2, 'b', Duration::from_secs(1));
+        session.insert(&3, 'c');
+        MockClock::advance(Duration::from_secs(1));
+        // inserting only reclaims expired records at the head
+        assert_eq!(session.insert_capacity(&4, 'd'), vec![(1, 'a')]);
+        assert_eq!(session.vacuum_count(), 1);
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&3, &4]);
+    }
+
+    #[test]
+    fn test_upsert_with() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        for _ in 0..3 {
+            session.upsert_with(&1, 1, |count| *count += 1);
+        }
+        session.upsert_with(&2, 1, |count| *count += 1);
+        assert_eq!(session.peek(&1), Some(3));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&1, &2]);
+
+        session.upsert_with(&1, 1, |count| *count += 1);
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2, &1]);
+
+        MockClock::advance(Duration::from_secs(1));
+        session.upsert_with(&1, 1, |count| *count += 1);
+        assert_eq!(session.peek(&1), Some(1));
+    }
+
+    #[test]
+    fn test_get_or_insert_with() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = Uuid::new_v4();
+        assert_eq!(session.get_or_insert_with(&k1, || 1), 1);
+        assert_eq!(session.get_or_insert_with(&k1, || 2), 1);
+        assert_eq!(session.len(), 1);
+
+        MockClock::advance(Duration::from_secs(2));
+        assert_eq!(session.get_or_insert_with(&k1, || 3), 3);
+        assert_eq!(session.fetch(&k1), Some(3));
+        assert_eq!(session.len(), 1);
+    }
+
+    #[test]
+    fn test_entry_or_insert_with() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        assert_eq!(session.entry_or_insert_with(&1, || 'a'), ('a', false));
+        assert_eq!(session.entry_or_insert_with(&1, || 'b'), ('a', true));
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.entry_or_insert_with(&1, || 'c'), ('c', false));
+        assert_eq!(session.len(), 1);
+    }
+
+    #[test]
+    fn test_get_or_try_insert_with() {
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = Uuid::new_v4();
+        assert_eq!(
+            session.get_or_try_insert_with(&k1, || Err("down")),
+            Err("down")
+        );
+        assert!(!session.contains_key(&k1));
+        assert!(session.is_empty());
+
+        assert_eq!(
+            session.get_or_try_insert_with(&k1, || Ok::<_, &str>(1)),
+            Ok(1)
+        );
+        assert_eq!(session.get_or_try_insert_with(&k1, || Err("down")), Ok(1));
+        assert_eq!(session.len(), 1);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        let k3 = session.insert_new(Uuid::new_v4, 3);
+        session.fetch(&k1);
+
+        assert_eq!(
+            session.into_iter().collect::<Vec<_>>(),
+            vec![(k2, 2), (k3, 3), (k1, 1)]
+        );
+    }
+
+    #[test]
+    fn test_from_map() {
+        MockClock::set_time(Duration::ZERO);
+        let map = HashMap::from([(1, 'a'), (2, 'b'), (3, 'c')]);
+        let mut session = TLRUCache::from_map(Duration::from_secs(1), map);
+        assert_eq!(session.len(), 3);
+        assert_eq!(session.stats().insertions, 3);
+        assert_eq!(session.fetch(&2), Some('b'));
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.fetch(&1), None);
+        assert_eq!(session.fetch(&3), None);
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let mut session: TLRUCache<_, _> = vec![(1, 'a'), (2, 'b'), (3, 'c')].into_iter().collect();
+        assert_eq!(session.expiry(), Duration::MAX);
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec!['a', 'b', 'c']
+        );
+
+        session.extend(vec![(4, 'd'), (1, 'e')]);
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec!['b', 'c', 'd', 'e']
+        );
+
+        let session = TLRUCache::from_iter_with_expiry(Duration::from_secs(1), vec![(1, 'a')]);
+        assert_eq!(session.expiry(), Duration::from_secs(1));
+        assert_eq!(session.len(), 1);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        MockClock::advance(Duration::from_secs(1));
+        session.insert(&2, 'b');
+        MockClock::advance(Duration::from_millis(1500));
+
+        assert_eq!(
+            session.snapshot(),
+            vec![
+                (1, 'a', Duration::ZERO),
+                (2, 'b', Duration::from_millis(500))
+            ]
+        );
+        assert_eq!(session.len(), 2);
     }
 
     #[test]
-    fn test_fetch() {
-        let mut session = TLRUCache::new(Duration::ZERO);
-        let k1 = session.insert_new(|| Uuid::new_v4(), 1);
-        let k2 = session.insert_new(|| Uuid::new_v4(), 2);
-        let k3 = session.insert_new(|| Uuid::new_v4(), 3);
-        assert_eq!(session.remove(&k2), Some(2));
-        assert_eq!(session.remove(&k2), None);
+    fn test_iter_with_ttl() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        session.insert_with_ttl(&2, 'b', Duration::from_secs(5));
+        MockClock::advance(Duration::from_secs(3));
+
+        assert_eq!(
+            session.iter_with_ttl().collect::<Vec<_>>(),
+            vec![
+                (&1, &'a', Duration::ZERO),
+                (&2, &'b', Duration::from_secs(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expiring_within() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(3));
+        session.insert(&1, 'a');
+        MockClock::advance(Duration::from_secs(1));
+        session.insert(&2, 'b');
+        MockClock::advance(Duration::from_secs(1));
+        session.insert(&3, 'c');
+
+        let soon = |session: &TLRUCache<i32, char>| {
+            session
+                .expiring_within(Duration::from_secs(2))
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(soon(&session), vec![1, 2]);
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(soon(&session), vec![2, 3]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(10));
+        session.insert(&1, 'a');
+        MockClock::advance(Duration::from_secs(4));
+        session.insert_with_ttl(&2, 'b', Duration::from_secs(3));
+        session.insert_with_ttl(&3, 'c', Duration::ZERO);
+        let bytes = bincode::serialize(&session).unwrap();
+
+        MockClock::set_time(Duration::ZERO);
+        let mut restored: TLRUCache<i32, char> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(restored.expiry(), Duration::from_secs(10));
+        assert_eq!(restored.keys().collect::<Vec<_>>(), vec![&1, &2]);
+        assert_eq!(restored.ttl(&1), Some(Duration::from_secs(6)));
+        assert_eq!(restored.ttl(&2), Some(Duration::from_secs(3)));
+        MockClock::advance(Duration::from_secs(3));
+        assert_eq!(restored.fetch(&2), None);
+        assert_eq!(restored.fetch(&1), Some('a'));
+
+        let mut session = TLRUCacheBuilder::new()
+            .max_lifetime(Duration::from_secs(10))
+            .build();
+        session.insert(&1, 'a');
+        MockClock::advance(Duration::from_secs(4));
+        let bytes = bincode::serialize(&session).unwrap();
+        let restored: TLRUCache<i32, char> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(restored.ttl(&1), Some(Duration::from_secs(6)));
+
+        let mut session = TLRUCache::new(Duration::from_secs(10));
+        session.insert(&1, 'a');
+        session.insert_with_ttl(&2, 'b', Duration::from_secs(5));
+        MockClock::advance(Duration::from_secs(4));
+        let bytes = bincode::serialize(&session).unwrap();
+        MockClock::advance(Duration::from_secs(20));
+        let mut restored: TLRUCache<i32, char> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(restored.ttl(&1), Some(Duration::from_secs(6)));
+        assert_eq!(restored.ttl(&2), Some(Duration::from_secs(1)));
+        restored.fetch(&1);
+        restored.fetch(&2);
+        assert_eq!(restored.ttl(&1), Some(Duration::from_secs(10)));
+        assert_eq!(restored.ttl(&2), Some(Duration::from_secs(5)));
+        restored.set_expiry(Duration::from_secs(60));
+        assert_eq!(restored.ttl(&1), Some(Duration::from_secs(60)));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_iter() {
+        use rayon::iter::ParallelIterator;
+
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        session.extend((0..1000u64).map(|i| (i, i)));
+        assert_eq!(session.par_iter().map(|x| x.value).sum::<u64>(), 499500);
+    }
+
+    #[test]
+    fn test_keys_values() {
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        session.fetch(&k1);
+
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&k2, &k1]);
+        assert_eq!(session.values().collect::<Vec<_>>(), vec![&2, &1]);
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        session.insert_new(Uuid::new_v4, 2);
+        for value in session.iter_mut() {
+            *value += 10;
+        }
+
         assert_eq!(
             session.iter().map(|x| x.value).collect::<Vec<_>>(),
-            vec![1, 3]
+            vec![11, 12]
         );
+        assert_eq!(session.fetch(&k1), Some(11));
+    }
 
-        assert_eq!(session.fetch(&k1), Some(1));
+    #[test]
+    fn test_retain() {
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        session.insert_new(Uuid::new_v4, 3);
+        session.insert_new(Uuid::new_v4, 4);
+
+        session.retain(|_, value| value % 2 == 0);
         assert_eq!(
             session.iter().map(|x| x.value).collect::<Vec<_>>(),
-            vec![3, 1]
+            vec![2, 4]
         );
+        assert_eq!(session.len(), 2);
+        assert_eq!(session.fetch(&k1), None);
+        assert_eq!(session.fetch(&k2), Some(2));
+    }
 
-        assert_eq!(session.fetch(&k3), Some(3));
+    #[test]
+    fn test_drain() {
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        session.insert_new(Uuid::new_v4, 3);
+
+        let mut drain = session.drain();
+        assert_eq!(drain.next(), Some((k1, 1)));
+        assert_eq!(drain.next(), Some((k2, 2)));
+        drop(drain);
+        assert!(session.is_empty());
         assert_eq!(
             session.iter().map(|x| x.value).collect::<Vec<_>>(),
-            vec![1, 3]
+            Vec::new()
+        );
+
+        session.insert(&k1, 4);
+        assert_eq!(session.drain().collect::<Vec<_>>(), vec![(k1, 4)]);
+        assert!(session.is_empty());
+    }
+
+    #[test]
+    fn test_stats() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::with_capacity(Duration::from_secs(2), 2);
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        session.insert(&k1, 3);
+        session.fetch(&k1);
+        session.fetch(&Uuid::new_v4());
+        session.insert_new(Uuid::new_v4, 4);
+        assert_eq!(session.fetch(&k2), None);
+        assert_eq!(
+            session.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 2,
+                insertions: 4,
+                evictions: 1,
+            }
+        );
+
+        session.reset_stats();
+        MockClock::advance(Duration::from_secs(2));
+        session.fetch(&k1);
+        session.vacuum();
+        assert_eq!(
+            session.stats(),
+            CacheStats {
+                hits: 0,
+                misses: 1,
+                insertions: 0,
+                evictions: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let clock = ManualClock::default();
+        let mut session = TLRUCacheBuilder::new()
+            .expiry(Duration::from_secs(2))
+            .capacity(2)
+            .hasher(BuildHasherDefault::<DefaultHasher>::default())
+            .clock(clock.clone())
+            .build();
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        session.insert(&3, 'c');
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2, &3]);
+
+        clock.set(Duration::from_secs(2));
+        assert_eq!(session.expiry(), Duration::from_secs(2));
+        assert_eq!(session.fetch(&2), None);
+
+        let session: TLRUCache<u32, u32> = TLRUCacheBuilder::new().build();
+        assert_eq!(session.expiry(), Duration::MAX);
+    }
+
+    #[test]
+    fn test_try_insert_new() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        let next = Cell::new(0);
+        let gen = || {
+            next.set((next.get() + 1) % 2);
+            next.get()
+        };
+        assert_eq!(session.try_insert_new(gen, 'a', 32), Ok(1));
+        assert_eq!(session.try_insert_new(gen, 'b', 32), Ok(0));
+        assert_eq!(session.try_insert_new(gen, 'c', 32), Err('c'));
+        assert_eq!(session.len(), 2);
+    }
+
+    #[test]
+    fn test_merge() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+
+        let mut other = TLRUCache::new(Duration::from_secs(1));
+        other.insert(&3, 'c');
+        MockClock::advance(Duration::from_secs(1));
+        other.insert(&1, 'd');
+        other.insert_with_ttl(&4, 'e', Duration::from_secs(5));
+
+        session.merge(other);
+        assert_eq!(
+            session.snapshot(),
+            vec![
+                (2, 'b', Duration::from_secs(1)),
+                (1, 'd', Duration::from_secs(2)),
+                (4, 'e', Duration::from_secs(5))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_negative_caching() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, Some('a'));
+        session.insert_absent(&2, Duration::from_secs(1));
+        assert_eq!(session.fetch_result(&1), CacheResult::Hit('a'));
+        assert_eq!(session.fetch_result(&2), CacheResult::NegativeHit);
+        assert_eq!(session.fetch_result(&3), CacheResult::Miss);
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.fetch_result(&2), CacheResult::Miss);
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.vacuum_count(), 1);
+        assert!(session.is_empty());
+    }
+
+    #[test]
+    fn test_try_insert() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        assert_eq!(session.try_insert(&1, 'a'), Ok(()));
+        assert_eq!(session.try_insert(&1, 'b'), Err('b'));
+        assert_eq!(session.peek(&1), Some('a'));
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.try_insert(&1, 'c'), Ok(()));
+        assert_eq!(session.peek(&1), Some('c'));
+        assert_eq!(session.len(), 1);
+    }
+
+    #[test]
+    fn test_max_lifetime() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCacheBuilder::new()
+            .expiry(Duration::from_secs(30))
+            .max_lifetime(Duration::from_secs(100))
+            .build();
+        assert_eq!(session.max_lifetime(), Some(Duration::from_secs(100)));
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+
+        for _ in 0..4 {
+            MockClock::advance(Duration::from_secs(20));
+            assert_eq!(session.fetch(&1), Some('a'));
+        }
+        assert_eq!(session.fetch(&2), None);
+        assert_eq!(session.ttl(&1), Some(Duration::from_secs(20)));
+
+        MockClock::advance(Duration::from_secs(20));
+        assert_eq!(session.fetch(&1), None);
+
+        session.set_max_lifetime(None);
+        session.insert(&3, 'c');
+        for _ in 0..10 {
+            MockClock::advance(Duration::from_secs(20));
+            assert_eq!(session.fetch(&3), Some('c'));
+        }
+    }
+
+    #[test]
+    fn test_absolute_policy() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCacheBuilder::new()
+            .expiry(Duration::from_secs(2))
+            .policy(ExpiryPolicy::Absolute)
+            .build();
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.fetch(&1), Some('a'));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2, &1]);
+        assert_eq!(session.ttl(&1), Some(Duration::from_secs(1)));
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.fetch(&1), None);
+
+        session.insert(&2, 'c');
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.fetch(&2), Some('c'));
+    }
+
+    #[test]
+    fn test_eq() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        let mut other = TLRUCache::new(Duration::from_secs(2));
+        other.insert(&2, 'b');
+        other.insert(&1, 'a');
+        assert_eq!(session, other);
+
+        other.insert(&1, 'c');
+        assert_ne!(session, other);
+        other.remove(&1);
+        assert_ne!(session, other);
+    }
+
+    #[test]
+    fn test_debug_stable() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&2, 'b');
+        session.insert(&1, 'a');
+        let before = format!("{:?}", session.debug_stable());
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(format!("{:?}", session.debug_stable()), before);
+        assert_eq!(before, "{2: 'b', 1: 'a'}");
+    }
+
+    #[test]
+    fn test_display() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(30));
+        assert_eq!(session.to_string(), "tlru(0 entries, expiry=30s)");
+
+        session.insert(&1, 'a');
+        MockClock::advance(Duration::from_secs(18));
+        session.insert(&2, 'b');
+        assert_eq!(
+            session.to_string(),
+            "tlru(2 entries, expiry=30s): 1=a (12s left), 2=b (30s left)"
+        );
+
+        session.extend((3..13).map(|i| (i, 'c')));
+        assert!(session
+            .to_string()
+            .ends_with(", 10=c (30s left), ... 2 more"));
+    }
+
+    #[test]
+    fn test_clone() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, String::from("a"));
+        session.insert(&2, String::from("b"));
+        MockClock::advance(Duration::from_secs(1));
+
+        let mut copy = session.clone();
+        assert_eq!(copy.ttl(&1), Some(Duration::from_secs(1)));
+        copy.get_mut(&1).unwrap().push('c');
+        copy.remove(&2);
+        copy.insert(&3, String::from("d"));
+
+        assert_eq!(
+            session.iter().map(|x| x.value.as_str()).collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            copy.iter().map(|x| x.value.as_str()).collect::<Vec<_>>(),
+            vec!["ac", "d"]
         );
+
+        let (sender, receiver) = mpsc::channel();
+        let session =
+            TLRUCache::with_capacity(Duration::from_secs(2), 1).with_eviction_sender(sender);
+        session.clone().extend([(1, 'a'), (2, 'b')]);
+        assert_eq!(receiver.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_remove_many() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        session.insert(&3, 'c');
+
+        assert_eq!(session.remove_many(&[1, 3, 4, 1]), 2);
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2]);
+    }
+
+    #[test]
+    fn test_peek_lru_mru() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        assert_eq!(session.peek_lru(), None);
+        assert_eq!(session.peek_mru(), None);
+
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        session.insert(&3, 'c');
+        assert_eq!(session.peek_lru(), Some((&1, &'a')));
+        assert_eq!(session.peek_mru(), Some((&3, &'c')));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&1, &2, &3]);
+    }
+
+    #[test]
+    fn test_pop_lru() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        session.fetch(&1);
+
+        assert_eq!(session.pop_lru(), Some((2, 'b')));
+        assert_eq!(session.pop_lru(), Some((1, 'a')));
+        assert_eq!(session.pop_lru(), None);
+        assert!(session.is_empty());
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        session.reserve(100);
+        assert!(session.store.capacity() >= 100);
+        session.insert(&1, 'a');
+        assert_eq!(session.fetch(&1), Some('a'));
+    }
+
+    #[test]
+    fn test_with_hashmap_capacity() {
+        let mut session = TLRUCache::with_hashmap_capacity(Duration::from_secs(1), 100);
+        assert!(session.store.capacity() >= 100);
+        session.extend((0..200).map(|i| (i, i)));
+        assert_eq!(session.len(), 200);
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut session = TLRUCache::new(Duration::from_secs(1));
+        session.extend((0..1000).map(|i| (i, i)));
+        session.retain(|k, _| *k < 10);
+        session.shrink_to_fit();
+        assert!(session.store.capacity() < 1000);
+        assert_eq!(session.len(), 10);
+    }
+
+    #[test]
+    fn test_len() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        assert!(session.is_empty());
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        session.insert_new(Uuid::new_v4, 2);
+        assert_eq!(session.len(), 2);
+
+        MockClock::advance(Duration::from_secs(2));
+        assert_eq!(session.len(), 2);
+        session.vacuum();
+        assert_eq!(session.len(), 0);
+        assert!(session.is_empty());
+        assert_eq!(session.remove(&k1), None);
+    }
+
+    #[test]
+    fn test_live_len() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert_with_ttl(&1, 'a', Duration::from_secs(4));
+        session.insert_with_ttl(&2, 'b', Duration::from_secs(1));
+        session.insert(&3, 'c');
+        assert_eq!(session.live_len(), 3);
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.live_len(), 2);
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.live_len(), 1);
+        assert_eq!(session.len(), 3);
     }
 
     #[test]
     fn test_vacuum() {
         MockClock::set_time(Duration::ZERO);
         let mut session = TLRUCache::new(Duration::from_secs(2));
-        session.insert_new(|| Uuid::new_v4(), 1);
+        session.insert_new(Uuid::new_v4, 1);
         MockClock::advance(Duration::from_millis(500));
-        let k2 = session.insert_new(|| Uuid::new_v4(), 2);
+        let k2 = session.insert_new(Uuid::new_v4, 2);
         MockClock::advance(Duration::from_millis(500));
-        session.insert_new(|| Uuid::new_v4(), 3);
+        session.insert_new(Uuid::new_v4, 3);
         MockClock::advance(Duration::from_millis(500));
         // 1: 0.0, 2: 0.5, 3: 1.0
         session.vacuum().fetch(&k2);
@@ -236,7 +3580,7 @@ mod test {
         );
 
         MockClock::advance(Duration::from_millis(700));
-        session.vacuum().insert_new(|| Uuid::new_v4(), 4);
+        session.vacuum().insert_new(Uuid::new_v4, 4);
         // 3: 1.0, 2: 1.5, 4: 2.2
         assert_eq!(
             session.iter().map(|x| x.value).collect::<Vec<_>>(),
@@ -261,4 +3605,232 @@ mod test {
             Vec::new()
         );
     }
+
+    #[test]
+    fn test_vacuum_count() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert_new(Uuid::new_v4, 1);
+        session.insert_new(Uuid::new_v4, 2);
+        MockClock::advance(Duration::from_secs(1));
+        session.insert_new(Uuid::new_v4, 3);
+        assert_eq!(session.vacuum_count(), 0);
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.vacuum_count(), 2);
+        assert_eq!(session.vacuum_count(), 0);
+        assert_eq!(session.len(), 1);
+    }
+
+    #[test]
+    fn test_iter_live() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        session.insert_with_ttl(&2, 'b', Duration::from_millis(500));
+        session.insert(&3, 'c');
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(
+            session.iter_live().map(|x| x.value).collect::<Vec<_>>(),
+            vec!['a', 'c']
+        );
+        assert_eq!(session.len(), 3);
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.iter_live().count(), 0);
+    }
+
+    #[test]
+    fn test_drain_expired() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        for i in 0..3 {
+            session.insert(&i, i);
+        }
+        MockClock::advance(Duration::from_secs(1));
+        session.insert(&3, 3);
+        MockClock::advance(Duration::from_secs(1));
+
+        assert_eq!(session.drain_expired().next(), Some((0, 0)));
+        assert_eq!(session.len(), 3);
+        assert_eq!(
+            session.drain_expired().collect::<Vec<_>>(),
+            vec![(1, 1), (2, 2)]
+        );
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&3]);
+        assert_eq!(session.drain_expired().next(), None);
+    }
+
+    #[test]
+    fn test_vacuum_all() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        session.insert(&1, 'a');
+        session.insert_with_ttl(&2, 'b', Duration::from_millis(500));
+        session.insert(&3, 'c');
+        session.insert_with_ttl(&4, 'd', Duration::from_millis(500));
+
+        MockClock::advance(Duration::from_secs(1));
+        session.vacuum();
+        assert_eq!(session.len(), 4);
+        session.vacuum_all();
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&1, &3]);
+        assert_eq!(session.stats().evictions, 2);
+    }
+
+    #[test]
+    fn test_vacuum_limited() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        for i in 0..5 {
+            session.insert_new(Uuid::new_v4, i);
+        }
+        MockClock::advance(Duration::from_secs(1));
+        session.insert_new(Uuid::new_v4, 5);
+        MockClock::advance(Duration::from_secs(1));
+
+        assert_eq!(session.vacuum_limited(2), 2);
+        assert_eq!(
+            session.iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec![2, 3, 4, 5]
+        );
+        assert_eq!(session.vacuum_limited(0), 0);
+        assert_eq!(session.vacuum_limited(10), 3);
+        assert_eq!(session.iter().map(|x| x.value).collect::<Vec<_>>(), vec![5]);
+    }
+
+    #[test]
+    fn test_vacuum_until() {
+        let clock = ManualClock::ticking(Duration::from_millis(1));
+        let mut session = TLRUCache::with_clock(Duration::from_millis(1), clock.clone());
+        for i in 0..10 {
+            session.insert(&i, i);
+        }
+        // every eviction reads the clock twice: deadline check and expiry check
+        let deadline = clock.get() + Duration::from_millis(6);
+        assert_eq!(session.vacuum_until(deadline), 3);
+        assert_eq!(session.len(), 7);
+        assert_eq!(session.vacuum_until(clock.get()), 0);
+        assert_eq!(session.vacuum_until(Duration::MAX), 7);
+    }
+
+    #[test]
+    fn test_eviction_sender() {
+        MockClock::set_time(Duration::ZERO);
+        let (sender, receiver) = mpsc::channel();
+        let mut session = TLRUCache::new(Duration::from_secs(1)).with_eviction_sender(sender);
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        MockClock::advance(Duration::from_secs(1));
+        session.insert(&3, 'c');
+        assert_eq!(session.vacuum_count(), 2);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                (1, 'a', EvictionCause::Expired),
+                (2, 'b', EvictionCause::Expired)
+            ]
+        );
+
+        drop(receiver);
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.vacuum_count(), 1);
+        assert!(session.is_empty());
+
+        let (sender, receiver) = mpsc::channel();
+        let mut session = TLRUCache::new(Duration::from_secs(1)).with_eviction_sender(sender);
+        session.insert(&1, 'a');
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.fetch(&1), None);
+        assert_eq!(session.stats().evictions, 1);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![(1, 'a', EvictionCause::Expired)]
+        );
+
+        let (sender, receiver) = mpsc::channel();
+        let mut session =
+            TLRUCache::with_capacity(Duration::from_secs(1), 1).with_eviction_sender(sender);
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        session.pop_lru();
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![(1, 'a', EvictionCause::Capacity)]
+        );
+
+        let (sender, receiver) = mpsc::channel();
+        let mut session = TLRUCache::with_weigher(Duration::from_secs(1), 1, |_: &char| 1)
+            .with_eviction_sender(sender);
+        session.insert(&1, 'a');
+        session.insert(&2, 'b');
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![(1, 'a', EvictionCause::Weight)]
+        );
+        assert_eq!(session.len(), 1);
+    }
+
+    #[test]
+    fn test_vacuum_callback() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        let k1 = session.insert_new(Uuid::new_v4, 1);
+        MockClock::advance(Duration::from_millis(500));
+        let k2 = session.insert_new(Uuid::new_v4, 2);
+        MockClock::advance(Duration::from_millis(500));
+        session.insert_new(Uuid::new_v4, 3);
+        MockClock::advance(Duration::from_millis(1600));
+
+        let mut evicted = Vec::new();
+        session.vacuum_callback(|rec| evicted.push((rec.key, rec.value)));
+        assert_eq!(evicted, vec![(k1, 1), (k2, 2)]);
+        assert_eq!(session.iter().map(|x| x.value).collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[test]
+    fn test_vacuum_with() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = TLRUCache::new(Duration::from_secs(2));
+        for i in 0..4 {
+            session.insert(&i, i);
+        }
+        session.insert_with_ttl(&4, 4, Duration::ZERO);
+        MockClock::advance(Duration::from_secs(2));
+
+        let mut offered = Vec::new();
+        session.vacuum_with(|rec| {
+            offered.push(rec.key);
+            if rec.value % 2 == 0 {
+                VacuumAction::Keep
+            } else {
+                VacuumAction::Evict
+            }
+        });
+        assert_eq!(offered, vec![0, 1, 2, 3, 4]);
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&0, &2, &4]);
+        assert_eq!(session.fetch(&0), Some(0));
+        assert_eq!(session.fetch(&4), None);
+        assert_eq!(session.stats().evictions, 3);
+
+        let mut session = TLRUCache::new(Duration::from_secs(2)).with_jitter_seed(0.0, 0);
+        session.insert_with_ttl(&1, 1, Duration::from_secs(1));
+        session.insert(&2, 2);
+        session.insert_with_ttl(&3, 3, Duration::from_secs(1));
+        session.insert_with_ttl(&4, 4, Duration::from_secs(1));
+        MockClock::advance(Duration::from_secs(1));
+
+        let mut offered = Vec::new();
+        session.vacuum_with(|rec| {
+            offered.push(rec.key);
+            if rec.key == 3 {
+                VacuumAction::Keep
+            } else {
+                VacuumAction::Evict
+            }
+        });
+        assert_eq!(offered, vec![1, 3, 4]);
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec![&2, &3]);
+    }
 }
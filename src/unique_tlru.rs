@@ -1,16 +1,31 @@
+use crate::clock::{Clock, DefaultClock};
 use crate::tlru::{self, Record, TLRUCache};
-use std::collections::HashMap;
-use std::hash::Hash;
-use std::time::Duration;
+use crate::{HashMap, RandomState};
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::time::Duration;
 
 pub trait Key {
     type K: Eq + PartialEq + Hash;
     fn id(&self) -> Self::K;
+
+    /// Borrowed form of `id`. Types storing their id should return it here so lookups skip
+    /// building an owned one, which is then only needed when a new value is inserted.
+    fn id_ref(&self) -> Option<&Self::K> {
+        None
+    }
+}
+
+fn with_id<V: Key, R>(value: &V, f: impl FnOnce(&V::K) -> R) -> R {
+    match value.id_ref() {
+        Some(id) => f(id),
+        None => f(&value.id()),
+    }
 }
 
-pub struct UniqueTLRUCache<K, V: Key> {
-    value_ids: HashMap<V::K, K>,
-    cache: TLRUCache<K, V>,
+pub struct UniqueTLRUCache<K, V: Key, C: Clock = DefaultClock, S = RandomState> {
+    value_ids: HashMap<V::K, K, S>,
+    cache: TLRUCache<K, V, C, S>,
 }
 
 pub struct Iter<'a, T> {
@@ -25,11 +40,41 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 }
 
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+#[cfg(feature = "std")]
 impl<K: Clone + Eq + Hash, V: Clone + Key> UniqueTLRUCache<K, V> {
     pub fn new(expiry: Duration) -> Self {
+        Self::with_hasher(expiry, RandomState::default())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Clone + Eq + Hash, V: Clone + Key, S: BuildHasher + Clone>
+    UniqueTLRUCache<K, V, DefaultClock, S>
+{
+    pub fn with_hasher(expiry: Duration, hasher: S) -> Self {
+        Self::from_parts(expiry, DefaultClock, hasher)
+    }
+}
+
+impl<K: Clone + Eq + Hash, V: Clone + Key, C: Clock> UniqueTLRUCache<K, V, C> {
+    pub fn with_clock(expiry: Duration, clock: C) -> Self {
+        Self::from_parts(expiry, clock, RandomState::default())
+    }
+}
+
+impl<K: Clone + Eq + Hash, V: Clone + Key, C: Clock, S: BuildHasher + Clone>
+    UniqueTLRUCache<K, V, C, S>
+{
+    fn from_parts(expiry: Duration, clock: C, hasher: S) -> Self {
         Self {
-            value_ids: HashMap::new(),
-            cache: TLRUCache::new(expiry),
+            value_ids: HashMap::with_hasher(hasher.clone()),
+            cache: TLRUCache::from_parts(expiry, clock, hasher),
         }
     }
 
@@ -37,59 +82,120 @@ impl<K: Clone + Eq + Hash, V: Clone + Key> UniqueTLRUCache<K, V> {
     where
         KF: Fn() -> K,
     {
-        let value_id = value.id();
-        if let Some(existing_id) = self.value_ids.get(&value_id) {
-            if let Some(_) = self.cache.fetch(existing_id) {
-                return existing_id.clone();
-            } else {
-                self.value_ids.remove(&value_id);
+        if let Some(existing_id) = with_id(&value, |id| self.value_ids.get(id).cloned()) {
+            if self.cache.fetch(&existing_id).is_some() {
+                return existing_id;
             }
         }
 
+        let value_id = value.id();
         let key = self.cache.insert_new(generate_random_key, value);
         self.value_ids.insert(value_id, key.clone());
         key
     }
 
-    pub fn fetch(&mut self, key: &K) -> Option<V> {
-        self.cache.fetch(key)
+    pub fn fetch<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let value_ids = &mut self.value_ids;
+        self.cache.fetch_with_expired(key, |val| {
+            with_id(val, |id| value_ids.remove(id));
+        })
+    }
+
+    /// Fetches the value whose `Key::id` is `id`.
+    pub fn fetch_by_id<Q>(&mut self, id: &Q) -> Option<V>
+    where
+        V::K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let value = self.cache.fetch(self.value_ids.get(id)?);
+        if value.is_none() {
+            self.value_ids.remove(id);
+        }
+        value
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        self.cache.remove(key).map(|val| {
-            self.value_ids.remove(&val.id());
-            val
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.remove(key).inspect(|val| {
+            with_id(val, |id| self.value_ids.remove(id));
         })
     }
 
+    pub fn remove_many<'a, Q>(&mut self, keys: impl IntoIterator<Item = &'a Q>) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + 'a,
+    {
+        keys.into_iter()
+            .filter(|key| self.remove(*key).is_some())
+            .count()
+    }
+
     pub fn remove_value(&mut self, value: &V) -> Option<V> {
-        self.value_ids
-            .remove(&value.id())
-            .and_then(|key| self.cache.remove(&key))
+        with_id(value, |id| self.value_ids.remove(id)).and_then(|key| self.cache.remove(&key))
     }
 
     pub fn vacuum(&mut self) -> &mut Self {
         self.cache.vacuum_callback(|rec| {
-            let value_id = rec.value.id();
-            self.value_ids.remove(&value_id);
+            with_id(&rec.value, |id| self.value_ids.remove(id));
         });
         self
     }
 
-    pub fn iter(&self) -> Iter<Record<K, V>> {
+    /// Whether a live record holds a value with the same `Key::id` as `value`. An expired
+    /// record found this way is dropped together with its id mapping.
+    pub fn contains_value(&mut self, value: &V) -> bool {
+        let Some(key) = with_id(value, |id| self.value_ids.get(id).cloned()) else {
+            return false;
+        };
+        if self.cache.contains_key(&key) {
+            return true;
+        }
+        with_id(value, |id| self.value_ids.remove(id));
+        self.cache.remove(&key);
+        false
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.value_ids.clear();
+    }
+
+    /// Number of stored records, including expired ones that have not been vacuumed yet.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_, Record<K, V, C::Instant>> {
         Iter {
             iter: self.cache.iter(),
         }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
     use std::time::Duration;
 
     use uuid::Uuid;
 
     use super::{Key, UniqueTLRUCache};
+    use crate::clock::ManualClock;
+
+    use mock_instant::thread_local::MockClock;
 
     #[derive(Clone)]
     struct MyVal(i32);
@@ -111,12 +217,131 @@ mod test {
         is_sync::<UniqueTLRUCache<Uuid, MyVal>>();
     }
 
+    #[test]
+    fn test_custom_clock() {
+        let clock = ManualClock::default();
+        let mut session = UniqueTLRUCache::with_clock(Duration::from_secs(2), clock.clone());
+        let k1 = session.insert_new(Uuid::new_v4, MyVal(1));
+        clock.set(Duration::from_secs(2));
+        let k2 = session.insert_new(Uuid::new_v4, MyVal(1));
+
+        assert_ne!(k1, k2);
+        assert_eq!(session.fetch(&k1).map(|x| x.0), None);
+        assert_eq!(session.fetch(&k2).map(|x| x.0), Some(1));
+    }
+
+    #[test]
+    fn test_fetch_by_id() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = UniqueTLRUCache::new(Duration::from_secs(1));
+        session.insert_new(Uuid::new_v4, MyVal(1));
+        assert_eq!(session.fetch_by_id(&1).map(|x| x.0), Some(1));
+        assert_eq!(session.fetch_by_id(&2).map(|x| x.0), None);
+
+        MockClock::advance(Duration::from_secs(1));
+        assert_eq!(session.fetch_by_id(&1).map(|x| x.0), None);
+        assert!(session.value_ids.is_empty());
+    }
+
+    #[test]
+    fn test_fetch_expired() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = UniqueTLRUCache::new(Duration::from_secs(1));
+        let keys = (0..100)
+            .map(|i| session.insert_new(Uuid::new_v4, MyVal(i)))
+            .collect::<Vec<_>>();
+
+        MockClock::advance(Duration::from_secs(1));
+        for key in &keys {
+            assert_eq!(session.fetch(key).map(|x| x.0), None);
+        }
+        assert_eq!(session.len(), 0);
+        assert!(session.value_ids.is_empty());
+    }
+
+    #[test]
+    fn test_contains_value() {
+        MockClock::set_time(Duration::ZERO);
+        let mut session = UniqueTLRUCache::new(Duration::from_secs(1));
+        assert!(session.is_empty());
+        session.insert_new(Uuid::new_v4, MyVal(1));
+        assert!(session.contains_value(&MyVal(1)));
+        assert!(!session.contains_value(&MyVal(2)));
+        assert_eq!(session.len(), 1);
+
+        MockClock::advance(Duration::from_secs(1));
+        assert!(!session.contains_value(&MyVal(1)));
+        assert!(session.value_ids.is_empty());
+        assert_eq!(session.len(), 0);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut session = UniqueTLRUCache::new(Duration::from_secs(1));
+        let k1 = session.insert_new(Uuid::new_v4, MyVal(1));
+        session.clear();
+        assert!(session.is_empty());
+        assert!(session.value_ids.is_empty());
+
+        let k2 = session.insert_new(Uuid::new_v4, MyVal(1));
+        assert_ne!(k1, k2);
+        assert_eq!(session.fetch(&k2).map(|x| x.0), Some(1));
+    }
+
+    #[test]
+    fn test_remove_many() {
+        let mut session = UniqueTLRUCache::new(Duration::from_secs(1));
+        let k1 = session.insert_new(Uuid::new_v4, MyVal(1));
+        let k2 = session.insert_new(Uuid::new_v4, MyVal(2));
+        session.insert_new(Uuid::new_v4, MyVal(3));
+
+        assert_eq!(session.remove_many(&[k1, k2, Uuid::new_v4()]), 2);
+        assert_ne!(session.insert_new(Uuid::new_v4, MyVal(1)), k1);
+        assert_eq!(
+            session.iter().map(|x| x.value.0).collect::<Vec<_>>(),
+            vec![3, 1]
+        );
+    }
+
+    #[test]
+    fn test_id_ref() {
+        #[derive(Clone)]
+        struct Named {
+            name: String,
+            owned_ids: Rc<Cell<usize>>,
+        }
+
+        impl Key for Named {
+            type K = String;
+
+            fn id(&self) -> String {
+                self.owned_ids.set(self.owned_ids.get() + 1);
+                self.name.clone()
+            }
+
+            fn id_ref(&self) -> Option<&String> {
+                Some(&self.name)
+            }
+        }
+
+        let owned_ids = Rc::new(Cell::new(0));
+        let named = Named {
+            name: String::from("a"),
+            owned_ids: owned_ids.clone(),
+        };
+        let mut session = UniqueTLRUCache::new(Duration::from_secs(1));
+        let k1 = session.insert_new(Uuid::new_v4, named.clone());
+        assert_eq!(session.insert_new(Uuid::new_v4, named.clone()), k1);
+        assert!(session.remove_value(&named).is_some());
+        assert_eq!(owned_ids.get(), 1);
+    }
+
     #[test]
     fn test_insert() {
-        let mut session = UniqueTLRUCache::new(Duration::ZERO);
-        let k1 = session.insert_new(|| Uuid::new_v4(), MyVal(1));
-        let k2 = session.insert_new(|| Uuid::new_v4(), MyVal(2));
-        let k3 = session.insert_new(|| Uuid::new_v4(), MyVal(1));
+        let mut session = UniqueTLRUCache::new(Duration::from_secs(1));
+        let k1 = session.insert_new(Uuid::new_v4, MyVal(1));
+        let k2 = session.insert_new(Uuid::new_v4, MyVal(2));
+        let k3 = session.insert_new(Uuid::new_v4, MyVal(1));
 
         assert_eq!(k1, k3);
         assert_ne!(k1, k2);